@@ -1,8 +1,8 @@
 //! Codefile represents one source code file and all of the files it imports
 
-use super::{Line, CompiledFiles, OpcodeOffset, CharOffset, LineNo, contract::{Contract, Find}, err::{LanguageError, NotFoundError}};
+use super::{Line, CompiledFiles, OpcodeOffset, CharOffset, LineNo, Declaration, ContractDecl, StructDecl, contract::{Contract, ContractFile, Find, ContractError}, err::{LanguageError, NotFoundError}};
 use failure::Error;
-use std::path::PathBuf;
+use std::{path::PathBuf, rc::Rc};
 
 // every CodeFile is associated with a language
 
@@ -37,6 +37,53 @@ impl CodeFile {
         &self.name
     }
 
+    /// Find a contract by name
+    pub fn contract(&self, contract: &str) -> Result<&Contract, Error> {
+        self.files.contracts().find(contract)
+    }
+
+    /// Every contract that was compiled
+    pub fn contracts(&self) -> &Vec<Contract> {
+        self.files.contracts()
+    }
+
+    /// Find a source file by the index the compiler gave it
+    pub fn file_by_id(&self, id: usize) -> Option<Rc<ContractFile>> {
+        self.files.files().iter().find(|f| f.id() == id).cloned()
+    }
+
+    /// Find a source file by its name, or by the end of its path
+    pub fn file_by_name(&self, name: &str) -> Option<Rc<ContractFile>> {
+        self.files.files()
+            .iter()
+            .find(|f| f.file_name() == name || f.file_path().ends_with(name))
+            .cloned()
+    }
+
+    /// Find the declaration of a contract in any of the source files
+    pub fn contract_decl(&self, contract: &str) -> Option<ContractDecl> {
+        self.files.files().iter().find_map(|f| f.contract_decl(contract))
+    }
+
+    /// Find the declaration of a struct in any of the source files
+    pub fn struct_decl(&self, name: &str) -> Option<StructDecl> {
+        self.files.files().iter().find_map(|f| f.struct_decl(name))
+    }
+
+    /// State variables of a contract, including those it inherits, in the order they are laid
+    /// out in storage. Constants are left out, since they are not stored
+    pub fn state_variables(&self, contract: &str) -> Result<Vec<Declaration>, Error> {
+        let decl = self.contract_decl(contract).ok_or_else(|| ContractError::NotFound(contract.to_string()))?;
+        // the most basic contract's variables come first
+        Ok(decl.bases
+            .iter()
+            .rev()
+            .filter_map(|id| self.files.files().iter().find_map(|f| f.contract_decl_by_id(*id)))
+            .flat_map(|base| base.state_variables.into_iter())
+            .filter(|var| !var.constant)
+            .collect())
+    }
+
     pub fn unique_exists(&self, lineno: LineNo, contract: &str) -> Result<bool, Error> {
         Ok(self.files.contracts()
             .find(contract)?
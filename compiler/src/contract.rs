@@ -1,10 +1,10 @@
 //! Contract Interface for Codefile/SourceMap/Debugger operations
-use super::{err::{LanguageError, NotFoundError}, Ast, SourceMap, AbstractFunction, AstItem, CharOffset};
+use super::{err::{LanguageError, NotFoundError}, Ast, SourceMap, AbstractFunction, AstItem, CharOffset, FunctionScope, ContractDecl, StructDecl};
 
 
 use ethereum_types::Address;
 use delegate::*;
-use std::{path::PathBuf, rc::Rc};
+use std::{path::{Path, PathBuf}, rc::Rc};
 use failure::{Fail, Error};
 use log::*;
 
@@ -71,6 +71,21 @@ impl ContractFile {
         self.source.as_str()
     }
 
+    /// index the compiler assigned to the source file. Source maps refer to files by this index
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// name of the source file
+    pub fn file_name(&self) -> &str {
+        self.file_name.as_str()
+    }
+
+    /// path to the source file
+    pub fn file_path(&self) -> &Path {
+        self.file_path.as_path()
+    }
+
     delegate! {
         target self.ast {
             pub fn variable(&self, name: &str) -> Result<AstItem, Error>;
@@ -78,10 +93,23 @@ impl ContractFile {
             pub fn function(&self, name: &str, fun: &mut FnMut(Result<&AbstractFunction, Error>) -> bool) -> Result<AstItem, Error>;
             pub fn find_contract(&self, offset: CharOffset) -> Option<AstItem>;
             pub fn find_function(&self, fun: &mut FnMut(&AbstractFunction) -> bool) -> Option<AstItem>;
+            pub fn function_scope(&self, offset: CharOffset) -> Option<FunctionScope>;
+            pub fn contract_decl(&self, name: &str) -> Option<ContractDecl>;
+            pub fn contract_decl_by_id(&self, id: usize) -> Option<ContractDecl>;
+            pub fn struct_decl(&self, name: &str) -> Option<StructDecl>;
         }
     }
 }
 
+/// Which bytecode of a contract is being executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CodeKind {
+    /// Initialization code that runs the constructor and returns the runtime bytecode
+    Creation,
+    /// Code deployed to the contracts address
+    Runtime,
+}
+
 /// Contract
 #[derive(Clone)]
 pub struct Contract {
@@ -90,6 +118,8 @@ pub struct Contract {
     abi: ethabi::Contract,
     runtime_bytecode: Vec<u8>,
     source_map: Rc<dyn SourceMap>,
+    creation_bytecode: Vec<u8>,
+    creation_source_map: Rc<dyn SourceMap>,
     addr: Address,
 }
 
@@ -124,11 +154,17 @@ impl Contract {
                map: Rc<dyn SourceMap>,
                abi: ethabi::Contract,
                addr: &Address,
-               runtime_bytecode: Vec<u8>) -> Result<Self, Error>
+               runtime_bytecode: Vec<u8>,
+               creation_map: Rc<dyn SourceMap>,
+               creation_bytecode: Vec<u8>) -> Result<Self, Error>
     {
-        trace!("Contract Instantiation Code Length: {}", runtime_bytecode.len());
+        trace!("Contract Instantiation Code Length: {}", creation_bytecode.len());
+        trace!("Contract Runtime Code Length: {}", runtime_bytecode.len());
         trace!("{:?}", runtime_bytecode);
-        Ok(Self { addr: addr.clone(), file, name, abi, runtime_bytecode, source_map: map })
+        Ok(Self {
+            addr: *addr, file, name, abi, runtime_bytecode, source_map: map,
+            creation_bytecode, creation_source_map: creation_map
+        })
     }
 
     pub fn name(&self) -> &str {
@@ -148,4 +184,54 @@ impl Contract {
     pub fn source_map(&self) -> Rc<dyn SourceMap> {
         self.source_map.clone()
     }
+
+    /// get a reference to the source map of the contracts' initialization code
+    pub fn creation_source_map(&self) -> Rc<dyn SourceMap> {
+        self.creation_source_map.clone()
+    }
+
+    /// get the source map for either the creation or runtime bytecode
+    pub fn source_map_for(&self, kind: CodeKind) -> Rc<dyn SourceMap> {
+        match kind {
+            CodeKind::Creation => self.creation_source_map(),
+            CodeKind::Runtime => self.source_map(),
+        }
+    }
+
+    /// Bytecode deployed on-chain
+    pub fn runtime_bytecode(&self) -> &[u8] {
+        self.runtime_bytecode.as_slice()
+    }
+
+    /// Bytecode that creates the contract, without any encoded constructor arguments
+    pub fn creation_bytecode(&self) -> &[u8] {
+        self.creation_bytecode.as_slice()
+    }
+
+    /// Which bytecode of this contract `code` is, if it is either.
+    /// Runtime code is compared without the metadata the compiler appends to it, and creation
+    /// code may have constructor arguments appended
+    pub fn code_kind(&self, code: &[u8]) -> Option<CodeKind> {
+        if !self.runtime_bytecode.is_empty() && without_metadata(code) == without_metadata(&self.runtime_bytecode) {
+            Some(CodeKind::Runtime)
+        } else if !self.creation_bytecode.is_empty() && code.starts_with(&self.creation_bytecode) {
+            Some(CodeKind::Creation)
+        } else {
+            None
+        }
+    }
+}
+
+// Solidity appends CBOR-encoded metadata to bytecode, followed by its length as two big-endian bytes.
+// The metadata always starts with 0xa1
+fn without_metadata(code: &[u8]) -> &[u8] {
+    if code.len() < 2 {
+        return code;
+    }
+    let len = ((code[code.len() - 2] as usize) << 8) | code[code.len() - 1] as usize;
+    if len + 2 <= code.len() && code[code.len() - len - 2] == 0xa1 {
+        &code[..code.len() - len - 2]
+    } else {
+        code
+    }
 }
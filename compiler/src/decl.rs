@@ -0,0 +1,205 @@
+//! Declarations of variables and functions, and the types of variables
+use super::{SourceRange, CharOffset};
+
+/// Where a reference type points to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Memory,
+    Storage,
+    Calldata,
+}
+
+/// Type of a variable
+#[derive(Debug, Clone, PartialEq)]
+pub enum VarType {
+    /// unsigned integer of `n` bits
+    Uint(usize),
+    /// signed integer of `n` bits
+    Int(usize),
+    Address,
+    Bool,
+    /// `bytesN`
+    FixedBytes(usize),
+    /// dynamically-sized `bytes`
+    Bytes,
+    String,
+    /// an array of elements, with a length if it is fixed-size
+    Array(Box<VarType>, Option<usize>),
+    Mapping(Box<VarType>, Box<VarType>),
+    /// a struct, by its name qualified with the contract that declares it
+    Struct(String),
+    Enum(String),
+    Contract(String),
+    /// any type that cannot be decoded (function types, etc)
+    Unknown(String),
+}
+
+impl VarType {
+    /// Parse a type from its description in the compiler output,
+    /// eg `uint256[] memory` or `mapping(address => uint256)`.
+    /// Returns the type and the location a reference type points to
+    pub fn parse(type_string: &str) -> (VarType, Option<Location>) {
+        let (ty, location) = strip_location(type_string.trim());
+        (Self::parse_type(ty), location)
+    }
+
+    fn parse_type(ty: &str) -> VarType {
+        let (ty, _) = strip_location(ty.trim());
+        if ty.ends_with(']') {
+            if let Some(open) = ty.rfind('[') {
+                let elem = Self::parse_type(&ty[..open]);
+                let len = ty[open + 1..ty.len() - 1].parse().ok();
+                return VarType::Array(Box::new(elem), len);
+            }
+        }
+        if ty.starts_with("mapping(") && ty.ends_with(')') {
+            let inner = &ty["mapping(".len()..ty.len() - 1];
+            if let Some(arrow) = inner.find(" => ") {
+                return VarType::Mapping(Box::new(Self::parse_type(&inner[..arrow])),
+                                        Box::new(Self::parse_type(&inner[arrow + 4..])));
+            }
+        }
+        if ty.starts_with("struct ") {
+            return VarType::Struct(ty["struct ".len()..].to_string());
+        }
+        if ty.starts_with("enum ") {
+            return VarType::Enum(ty["enum ".len()..].to_string());
+        }
+        if ty.starts_with("contract ") {
+            return VarType::Contract(ty["contract ".len()..].to_string());
+        }
+        match ty {
+            "address" | "address payable" => VarType::Address,
+            "bool" => VarType::Bool,
+            "string" => VarType::String,
+            "bytes" => VarType::Bytes,
+            "byte" => VarType::FixedBytes(1),
+            "uint" => VarType::Uint(256),
+            "int" => VarType::Int(256),
+            _ => {
+                if ty.starts_with("uint") {
+                    ty["uint".len()..].parse().map(VarType::Uint).unwrap_or_else(|_| VarType::Unknown(ty.to_string()))
+                } else if ty.starts_with("int") {
+                    ty["int".len()..].parse().map(VarType::Int).unwrap_or_else(|_| VarType::Unknown(ty.to_string()))
+                } else if ty.starts_with("bytes") {
+                    ty["bytes".len()..].parse().map(VarType::FixedBytes).unwrap_or_else(|_| VarType::Unknown(ty.to_string()))
+                } else {
+                    VarType::Unknown(ty.to_string())
+                }
+            }
+        }
+    }
+
+    /// Whether a variable of this type holds a pointer, rather than the value itself
+    pub fn is_reference(&self) -> bool {
+        match self {
+            VarType::Bytes | VarType::String | VarType::Array(..) | VarType::Mapping(..) | VarType::Struct(_) => true,
+            _ => false
+        }
+    }
+}
+
+// the compiler suffixes reference types with the location they point to
+fn strip_location(ty: &str) -> (&str, Option<Location>) {
+    const SUFFIXES: &[(&str, Location)] = &[
+        (" storage pointer", Location::Storage),
+        (" storage ref", Location::Storage),
+        (" storage", Location::Storage),
+        (" memory", Location::Memory),
+        (" calldata", Location::Calldata),
+    ];
+    for (suffix, location) in SUFFIXES.iter() {
+        if ty.ends_with(suffix) {
+            return (&ty[..ty.len() - suffix.len()], Some(*location));
+        }
+    }
+    (ty, None)
+}
+
+/// A variable declared in source code
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    pub name: String,
+    pub ty: VarType,
+    /// Where the variable points to, if it is a reference type
+    pub location: Option<Location>,
+    /// Location of the declaration in the source file
+    pub range: SourceRange,
+    /// The block a local variable is visible in. `None` for any other variable
+    pub scope: Option<SourceRange>,
+    /// constants are not stored anywhere
+    pub constant: bool,
+}
+
+impl Declaration {
+    /// Whether the variable is visible at byte `offset` of the source. A local variable is only
+    /// visible once it has been declared, inside the block it is declared in
+    pub fn in_scope(&self, offset: CharOffset) -> bool {
+        match self.scope {
+            Some((start, end)) => self.range.0 < offset && start <= offset && offset <= end,
+            None => true
+        }
+    }
+}
+
+/// A function and every variable declared in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionScope {
+    pub name: String,
+    /// Location of the function definition in the source file
+    pub location: SourceRange,
+    pub params: Vec<Declaration>,
+    pub returns: Vec<Declaration>,
+    /// variables declared in the body of the function, in the order they are declared
+    pub locals: Vec<Declaration>,
+}
+
+/// A contract and the state variables it declares
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractDecl {
+    /// identifies the contract across every source file in a compilation
+    pub id: usize,
+    pub name: String,
+    /// ids of the contracts this contract inherits from, in linearized order: the contract
+    /// itself first, and the most basic contract last
+    pub bases: Vec<usize>,
+    /// state variables declared by this contract alone, in the order they are declared
+    pub state_variables: Vec<Declaration>,
+}
+
+/// A struct and its members
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructDecl {
+    /// name qualified with the contract that declares it, ie `Ballot.Voter`
+    pub name: String,
+    pub members: Vec<Declaration>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculate::speculate;
+
+    speculate! {
+        it "parses value types" {
+            assert_eq!(VarType::parse("uint256"), (VarType::Uint(256), None));
+            assert_eq!(VarType::parse("int8"), (VarType::Int(8), None));
+            assert_eq!(VarType::parse("bytes32"), (VarType::FixedBytes(32), None));
+            assert_eq!(VarType::parse("address"), (VarType::Address, None));
+        }
+
+        it "parses reference types and their location" {
+            assert_eq!(VarType::parse("string memory"), (VarType::String, Some(Location::Memory)));
+            assert_eq!(VarType::parse("uint256[3] storage ref"),
+                       (VarType::Array(Box::new(VarType::Uint(256)), Some(3)), Some(Location::Storage)));
+            assert_eq!(VarType::parse("struct Ballot.Proposal memory[] memory"),
+                       (VarType::Array(Box::new(VarType::Struct("Ballot.Proposal".to_string())), None), Some(Location::Memory)));
+        }
+
+        it "parses mappings" {
+            assert_eq!(VarType::parse("mapping(address => mapping(uint256 => bool))"),
+                       (VarType::Mapping(Box::new(VarType::Address),
+                                         Box::new(VarType::Mapping(Box::new(VarType::Uint(256)), Box::new(VarType::Bool)))), None));
+        }
+    }
+}
@@ -10,6 +10,8 @@ pub enum LanguageError {
     ParseError,
     #[fail(display = "Path must be valid UTF-8")]
     InvalidPath,
+    #[fail(display = "Unknown hard fork `{}`", _0)]
+    UnknownFork(String),
     #[fail(display = "IO Error")]
     Io(#[fail(cause)] std::io::Error),
     // Language-specific Errors (Solidity, Vyper, LLL, etc)
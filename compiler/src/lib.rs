@@ -20,13 +20,16 @@
 mod err;
 mod types;
 mod contract;
+mod decl;
 pub mod map;
 mod code_file;
 pub mod solidity;
 // pub mod vyper;
 
 pub use self::code_file::CodeFile;
-pub use self::contract::{Contract, Find, ContractFile};
+pub use self::contract::{Contract, Find, ContractFile, CodeKind};
+pub use self::types::FoundationVersion;
+pub use self::decl::{Declaration, FunctionScope, ContractDecl, StructDecl, VarType, Location};
 
 use std::{path::PathBuf, rc::Rc};
 
@@ -73,6 +76,17 @@ pub type OpcodeOffset = usize;
 pub type CharOffset = usize;
 pub type SourceRange = (usize, usize);
 
+/// How a jump instruction moves between functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JumpKind {
+    /// Jumps into a function
+    IntoFunction,
+    /// Returns from a function
+    ReturnFromFunction,
+    /// Any other instruction, including jumps within a function
+    Regular,
+}
+
 //TODO: Can merge some of these functions by passing in an enum
 /// Represents a Source Map
 pub trait SourceMap {
@@ -112,6 +126,23 @@ pub trait SourceMap {
 
     /// Get the next `count` number of lines (inclusive) from opcode position/offset
     fn next_lines(&self, offset: OpcodeOffset, count: usize) -> Result<Vec<Line>, Error>;
+
+    /// Get the range of characters in the source file (start, end) an opcode position maps to
+    fn source_range(&self, offset: OpcodeOffset) -> Result<SourceRange, Error>;
+
+    /// Source index of the file an opcode position maps to. Lines and ranges of an opcode
+    /// position are in this file. Code the compiler generates maps to the contract's own file
+    fn source_index(&self, offset: OpcodeOffset) -> Result<usize, Error>;
+
+    /// Whether any instruction maps to a line of the file with source index `file`
+    fn has_line(&self, file: usize, lineno: LineNo) -> bool;
+
+    /// Whether the instruction at an opcode position maps to source, rather than being code the
+    /// compiler generated
+    fn has_source(&self, offset: OpcodeOffset) -> bool;
+
+    /// Whether the instruction at an opcode position enters or leaves a function
+    fn jump(&self, offset: OpcodeOffset) -> Result<JumpKind, Error>;
 }
 
 /// loosely and generally represents a Node in the Ast attached to a particular language item
@@ -180,5 +211,14 @@ pub trait Ast {
     /// closure and individual AST nodes may be accessed through it. Returns an AST item based on
     /// result of closure
     fn find_function(&self, fun: &mut FnMut(&AbstractFunction) -> bool) -> Option<AstItem>;
+    /// The innermost function whose definition contains the byte offset, with every variable
+    /// declared in it
+    fn function_scope(&self, offset: CharOffset) -> Option<FunctionScope>;
+    /// Find the declaration of a contract by name
+    fn contract_decl(&self, name: &str) -> Option<ContractDecl>;
+    /// Find the declaration of a contract by the id the compiler assigned it
+    fn contract_decl_by_id(&self, id: usize) -> Option<ContractDecl>;
+    /// Find the declaration of a struct by its name, qualified with the contract that declares it
+    fn struct_decl(&self, name: &str) -> Option<StructDecl>;
 }
 
@@ -1,24 +1,36 @@
 mod source_map;
 mod ast;
+mod json_ast;
 pub mod err;
 
 use std::{
     path::PathBuf,
     io::Read,
     iter::FromIterator,
+    collections::HashMap,
     rc::Rc,
 };
 
 use ethereum_types::Address;
 use failure::Error;
-use solc_api::{ SolcApiBuilder, types::FoundationVersion };
+use solc_api::SolcApiBuilder;
 use log::*;
 use self::{err::SolidityError, source_map::SoliditySourceMap, ast::SolidityAst};
-use super::{CompiledFiles, Language, contract::{ContractFile, Contract} };
+use super::{CompiledFiles, Language, FoundationVersion, map::Map, contract::{ContractFile, Contract} };
 
 /// A struct for Solidity Source Mapping
 #[derive(Debug, Clone, PartialEq, Default)]
-pub struct Solidity;
+pub struct Solidity {
+    /// hard fork to target when compiling
+    evm_version: FoundationVersion,
+}
+
+impl Solidity {
+    /// Compile Solidity for the rules of a specific hard fork
+    pub fn new(evm_version: FoundationVersion) -> Self {
+        Self { evm_version }
+    }
+}
 
 impl Language for Solidity {
 
@@ -31,10 +43,9 @@ impl Language for Solidity {
         let parent = path.parent().ok_or(SolidityError::ParentNotFound)?.to_path_buf();
         let compiled_source = SolcApiBuilder::default()
             .source_file(path)
-            .evm_version(FoundationVersion::Byzantium)
+            .evm_version(self.evm_version.clone().into())
             .compile();
-        let mut contracts = Vec::new();
-        let files = compiled_source
+        let sources = compiled_source
             .sources()
             .map(|(file, compiled_file)| {
                 let mut import_path = parent.clone();
@@ -43,23 +54,32 @@ impl Language for Solidity {
                 let file_buf = std::fs::File::open(import_path.as_path())?.read_to_string(&mut src)?;
                 info!("Read {} bytes from source file: {}", file_buf, file);
 
-                let ast = SolidityAst::new(&src)?;
-                let cfile = Rc::new(ContractFile::new(src, compiled_file.id, Rc::new(ast), import_path)?);
-                contracts.extend(compiled_source
-                    .contracts_by(|c| &c.file_name == file)
-                    .map(|c| {
-                        let deployed_code = c.evm.deployed_bytecode.as_ref().expect("Should never be missing field bytecode; qed").clone();
-                        Contract::new(cfile.clone(),
-                                      c.name.clone(),
-                                      Rc::new(SoliditySourceMap::new(cfile.clone().source(), deployed_code.source_map)),
-                                      c.abi.clone(),
-                                      address,
-                                      deployed_code.object
-                                      ).map_err(|e| e.into())
-                    }));
-                Ok(cfile)
+                let ast = SolidityAst::new(&src, compiled_file.ast.json())?;
+                Ok((file, Rc::new(ContractFile::new(src, compiled_file.id, Rc::new(ast), import_path)?)))
             })
-            .collect::<Result<Vec<Rc<ContractFile>>, Error>>()?;
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        // source maps of a contract may refer to any file, ie for inherited functions or libraries
+        let maps = Rc::new(sources.iter().map(|(_, f)| (f.id(), Map::new(f.source()))).collect::<HashMap<usize, Map>>());
+        let mut contracts = Vec::new();
+        for (file, cfile) in sources.iter() {
+            contracts.extend(compiled_source
+                .contracts_by(|c| &c.file_name == *file)
+                .map(|c| {
+                    let deployed_code = c.evm.deployed_bytecode.as_ref().expect("Should never be missing field bytecode; qed").clone();
+                    let creation_code = c.evm.bytecode.as_ref().expect("Should never be missing field bytecode; qed").clone();
+                    Contract::new(cfile.clone(),
+                                  c.name.clone(),
+                                  Rc::new(SoliditySourceMap::new(cfile.id(), maps.clone(), deployed_code.source_map)),
+                                  c.abi.clone(),
+                                  address,
+                                  deployed_code.object,
+                                  Rc::new(SoliditySourceMap::new(cfile.id(), maps.clone(), creation_code.source_map)),
+                                  creation_code.object
+                                  ).map_err(|e| e.into())
+                }));
+        }
+        let files = sources.into_iter().map(|(_, f)| f).collect::<Vec<Rc<ContractFile>>>();
         if contracts.len() == 0 {
             warn!("Possible error during compilation; no contracts compiled");
         }
@@ -80,6 +100,6 @@ mod test {
         pretty_env_logger::try_init();
         let mock = edbtest::MockWeb3Transport::default();
         let path = edbtest::contract_path(edbtest::Contract::Voting);
-        Solidity::compile(&Solidity, path, &edbtest::ethtype_addr(edbtest::SIMPLE_STORAGE_ADDR)).unwrap();
+        Solidity::compile(&Solidity::default(), path, &edbtest::ethtype_addr(edbtest::SIMPLE_STORAGE_ADDR)).unwrap();
     }
 }
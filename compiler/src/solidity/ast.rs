@@ -1,18 +1,21 @@
 use failure::Error;
 use log::*;
 use lunarity_ast::{Program, SourceUnit, ContractPart, FunctionDefinition};
-use super::err::SolidityError;
-use crate::{Ast, CharOffset, AstItem, AstType, AbstractFunction, Mutation, SourceRange};
+use super::{err::SolidityError, json_ast::JsonAst};
+use crate::{Ast, CharOffset, AstItem, AstType, AbstractFunction, Mutation, SourceRange, FunctionScope, ContractDecl, StructDecl};
 
 pub struct SolidityAst<'ast> {
     program: Program<'ast>,
+    /// declarations from the JSON AST the compiler outputs
+    decls: JsonAst,
 }
 
 impl<'ast> SolidityAst<'ast> {
-    pub fn new(source: &str) -> Result<Self, SolidityError> {
+    /// `json` is the AST of the same source that the compiler outputs
+    pub fn new(source: &str, json: &serde_json::Value) -> Result<Self, SolidityError> {
         let program: Program<'ast> = lunarity_parser::parse(source)
             .map_err(|e| SolidityError::AstParse(format!("{:?}", e)))?;
-        Ok(Self { program })
+        Ok(Self { program, decls: JsonAst::new(json) })
     }
 }
 
@@ -69,7 +72,7 @@ impl<'ast> Ast for SolidityAst<'ast> {
                                 if fun(&f as &AbstractFunction) {
                                     return Some(AstItem {
                                         variant: AstType::Function,
-                                        name: f.name.unwrap().value.to_string(),
+                                        name: f.name(),
                                         location: (cnode.start as usize, cnode.end as usize)
                                     });
                                 }
@@ -83,14 +86,30 @@ impl<'ast> Ast for SolidityAst<'ast> {
         }
         None
     }
+
+    fn function_scope(&self, offset: CharOffset) -> Option<FunctionScope> {
+        self.decls.function_scope(offset).cloned()
+    }
+
+    fn contract_decl(&self, name: &str) -> Option<ContractDecl> {
+        self.decls.contract(name).cloned()
+    }
+
+    fn contract_decl_by_id(&self, id: usize) -> Option<ContractDecl> {
+        self.decls.contract_by_id(id).cloned()
+    }
+
+    fn struct_decl(&self, name: &str) -> Option<StructDecl> {
+        self.decls.structure(name).cloned()
+    }
 }
 
 impl<'ast> AbstractFunction for FunctionDefinition<'ast> {
 
     /// Name of the function
     fn name(&self) -> String {
-        // TODO: dig through lunarity to figure out why name would ever be empty
-        self.name.expect("Name should never be empty; qed").value.to_string()
+        // the fallback function is the only function without a name
+        self.name.map(|n| n.value.to_string()).unwrap_or_else(|| String::from("fallback"))
     }
 
     /// Parameters of function
@@ -108,12 +127,11 @@ impl<'ast> AbstractFunction for FunctionDefinition<'ast> {
         unimplemented!();
     }
 
-    /// Source Location range of function
+    /// Source Location range of the function body. Functions without a body have an empty range
     fn location(&self) -> SourceRange {
-        (
-            self.block.expect("Should not be empty; qed").start as usize,
-            self.block.expect("Should not be empty; qed").end as usize
-        )
+        self.block
+            .map(|b| (b.start as usize, b.end as usize))
+            .unwrap_or((0, 0))
     }
 }
 
@@ -128,7 +146,7 @@ mod tests {
             #[allow(unused_must_use)] {
                 pretty_env_logger::try_init();
             }
-            let ast = SolidityAst::new(TEST_CONTRACT).unwrap();
+            let ast = SolidityAst::new(TEST_CONTRACT, &serde_json::Value::Null).unwrap();
         }
 
         it "can find a contract" {
@@ -0,0 +1,212 @@
+//! Declarations read from the JSON AST that the Solidity Compiler outputs
+use serde_json::Value;
+use crate::{CharOffset, SourceRange, decl::{Declaration, FunctionScope, ContractDecl, StructDecl, VarType}};
+
+#[derive(Debug, Clone, Default)]
+pub struct JsonAst {
+    functions: Vec<FunctionScope>,
+    contracts: Vec<ContractDecl>,
+    structs: Vec<StructDecl>,
+}
+
+impl JsonAst {
+    pub fn new(ast: &Value) -> Self {
+        let (mut functions, mut contracts, mut structs) = (Vec::new(), Vec::new(), Vec::new());
+        walk(ast, &mut |node| {
+            match node_type(node) {
+                Some("FunctionDefinition") => functions.extend(function(node)),
+                Some("ContractDefinition") => contracts.extend(contract(node)),
+                Some("StructDefinition") => structs.extend(structure(node)),
+                _ => ()
+            }
+        });
+        Self { functions, contracts, structs }
+    }
+
+    pub fn contract(&self, name: &str) -> Option<&ContractDecl> {
+        self.contracts.iter().find(|c| c.name == name)
+    }
+
+    pub fn contract_by_id(&self, id: usize) -> Option<&ContractDecl> {
+        self.contracts.iter().find(|c| c.id == id)
+    }
+
+    /// find a struct by the name qualified with its contract
+    pub fn structure(&self, name: &str) -> Option<&StructDecl> {
+        self.structs.iter().find(|s| s.name == name)
+    }
+
+    /// The innermost function whose definition contains `offset`
+    pub fn function_scope(&self, offset: CharOffset) -> Option<&FunctionScope> {
+        self.functions
+            .iter()
+            .filter(|f| f.location.0 <= offset && offset <= f.location.1)
+            .min_by_key(|f| f.location.1 - f.location.0)
+    }
+}
+
+// visit every node in the tree
+fn walk<F>(node: &Value, fun: &mut F) where F: FnMut(&Value) {
+    fun(node);
+    match node {
+        Value::Object(map) => {
+            for child in map.values() {
+                walk(child, fun);
+            }
+        },
+        Value::Array(arr) => {
+            for child in arr.iter() {
+                walk(child, fun);
+            }
+        },
+        _ => ()
+    }
+}
+
+fn node_type(node: &Value) -> Option<&str> {
+    node["nodeType"].as_str()
+}
+
+// `src` is of the form `start:length:source_index`
+fn src(node: &Value) -> Option<SourceRange> {
+    let mut parts = node["src"].as_str()?.split(':');
+    let start: usize = parts.next()?.parse().ok()?;
+    let length: usize = parts.next()?.parse().ok()?;
+    Some((start, start + length))
+}
+
+fn function(node: &Value) -> Option<FunctionScope> {
+    let name = match node["name"].as_str() {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => {
+            if node["isConstructor"].as_bool().unwrap_or(false) || node["kind"].as_str() == Some("constructor") {
+                "constructor".to_string()
+            } else {
+                "fallback".to_string()
+            }
+        }
+    };
+    let location = src(node)?;
+    // serde_json orders object keys alphabetically, so sort declarations back into source order
+    let mut locals = Vec::new();
+    local_declarations(&node["body"], location, &mut locals);
+    locals.sort_by_key(|d| d.range.0);
+
+    Some(FunctionScope {
+        name,
+        location,
+        params: parameters(&node["parameters"]),
+        returns: parameters(&node["returnParameters"]),
+        locals,
+    })
+}
+
+// every variable declared in `node`, visible in the innermost block around it. `block` is the
+// block `node` is in
+fn local_declarations(node: &Value, block: SourceRange, locals: &mut Vec<Declaration>) {
+    let block = match node_type(node) {
+        // the variables a `for` declares are only visible in the loop
+        Some("Block") | Some("ForStatement") => src(node).unwrap_or(block),
+        _ => block
+    };
+    if node_type(node) == Some("VariableDeclaration") {
+        locals.extend(declaration(node).map(|decl| Declaration { scope: Some(block), ..decl }));
+    }
+    match node {
+        Value::Object(map) => map.values().for_each(|child| local_declarations(child, block, locals)),
+        Value::Array(arr) => arr.iter().for_each(|child| local_declarations(child, block, locals)),
+        _ => ()
+    }
+}
+
+fn contract(node: &Value) -> Option<ContractDecl> {
+    let bases = node["linearizedBaseContracts"]
+        .as_array()
+        .map(|ids| ids.iter().filter_map(|id| id.as_u64()).map(|id| id as usize).collect())
+        .unwrap_or_default();
+    let state_variables = node["nodes"]
+        .as_array()
+        .map(|nodes| {
+            nodes.iter()
+                .filter(|n| node_type(n) == Some("VariableDeclaration"))
+                .filter_map(declaration)
+                .collect()
+        })
+        .unwrap_or_default();
+    Some(ContractDecl {
+        id: node["id"].as_u64()? as usize,
+        name: node["name"].as_str()?.to_string(),
+        bases,
+        state_variables,
+    })
+}
+
+fn structure(node: &Value) -> Option<StructDecl> {
+    Some(StructDecl {
+        name: node["canonicalName"].as_str()?.to_string(),
+        members: node["members"].as_array()?.iter().filter_map(declaration).collect(),
+    })
+}
+
+fn parameters(list: &Value) -> Vec<Declaration> {
+    list["parameters"]
+        .as_array()
+        .map(|params| params.iter().filter_map(declaration).collect())
+        .unwrap_or_default()
+}
+
+fn declaration(node: &Value) -> Option<Declaration> {
+    let type_string = node["typeDescriptions"]["typeString"].as_str()?;
+    let (ty, location) = VarType::parse(type_string);
+    Some(Declaration {
+        name: node["name"].as_str()?.to_string(),
+        ty,
+        location,
+        range: src(node)?,
+        scope: None,
+        constant: node["constant"].as_bool().unwrap_or(false),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculate::speculate;
+    use serde_json::json;
+
+    fn variable(name: &str, src: &str) -> Value {
+        json!({ "nodeType": "VariableDeclaration", "name": name, "src": src, "typeDescriptions": { "typeString": "uint256" } })
+    }
+
+    speculate! {
+        it "scopes local variables to the block they are declared in" {
+            // function f() { uint a; if (true) { uint b; } }
+            let ast = json!({
+                "nodeType": "FunctionDefinition",
+                "name": "f",
+                "src": "0:100:0",
+                "parameters": { "parameters": [] },
+                "returnParameters": { "parameters": [] },
+                "body": {
+                    "nodeType": "Block",
+                    "src": "10:90:0",
+                    "statements": [
+                        { "nodeType": "VariableDeclarationStatement", "src": "20:7:0", "declarations": [variable("a", "20:6:0")] },
+                        { "nodeType": "IfStatement", "src": "40:50:0", "trueBody": {
+                            "nodeType": "Block",
+                            "src": "50:30:0",
+                            "statements": [
+                                { "nodeType": "VariableDeclarationStatement", "src": "60:7:0", "declarations": [variable("b", "60:6:0")] }
+                            ]
+                        }}
+                    ]
+                }
+            });
+            let scope = JsonAst::new(&ast).function_scope(65).cloned().unwrap();
+            let (a, b) = (&scope.locals[0], &scope.locals[1]);
+            assert_eq!((a.scope, b.scope), (Some((10, 100)), Some((50, 80))));
+            assert!(!a.in_scope(20) && a.in_scope(30) && a.in_scope(90));
+            assert!(!b.in_scope(30) && !b.in_scope(60) && b.in_scope(70) && !b.in_scope(90));
+        }
+    }
+}
@@ -1,28 +1,45 @@
-use crate::{ SourceMap, Line, LineNo, CharOffset, OpcodeOffset, map::{Map, LineNumber} };
-use std::{iter::FromIterator, collections::HashMap };
+use crate::{ SourceMap, Line, LineNo, CharOffset, OpcodeOffset, SourceRange, JumpKind, map::{Map, LineNumber} };
+use std::{iter::FromIterator, collections::{HashMap, HashSet}, rc::Rc };
 use super::err::{SolidityError, SourceMapError};
-use solc_api::types::{Instruction, SourceIndex};
+use solc_api::types::{Instruction, SourceIndex, Jump};
 use log::*;
 use failure::Error;
 
 // TODO many of these Strings, Vec<> can be made references with a lifetime on ContractFile
 #[derive(Debug, Clone)]
 pub struct SoliditySourceMap {
-    /// simple map of the source itself
-    map: Map,
+    /// source index of the file the contract is defined in
+    file: usize,
+    /// simple maps of every source file in the compilation, by source index
+    maps: Rc<HashMap<usize, Map>>,
     /// Source map acquired from Solidity Compiler
     program_map: Vec<Instruction>,
-    line_cache: HashMap<usize, Option<usize>>
+    line_cache: HashMap<usize, Option<usize>>,
+    /// every line of every file that an instruction maps to, as (source index, line)
+    lines: HashSet<(usize, LineNo)>,
 }
 
 impl SoliditySourceMap {
 
-    pub fn new(src: &str, source_map: Vec<Instruction>) -> Self {
+    /// `maps` must contain the file the contract is defined in
+    pub fn new(file: usize, maps: Rc<HashMap<usize, Map>>, source_map: Vec<Instruction>) -> Self {
         let mut cache = HashMap::new();
-        let map = Map::new(src);
-        for (lineno, _) in src.lines().enumerate() {
-            cache.insert(lineno, Self::shortest_len(&source_map, &map, lineno).map(|i| i.position));
+        let map = maps.get(&file).expect("the contract's own source is always mapped; qed");
+        let own_instructions = source_map
+            .iter()
+            .filter(|i| Self::index(i, file) == file)
+            .cloned()
+            .collect::<Vec<Instruction>>();
+        for lineno in 0..map.len() {
+            cache.insert(lineno, Self::shortest_len(&own_instructions, map, lineno).map(|i| i.position));
         }
+        let lines = source_map
+            .iter()
+            .filter_map(|i| match i.source_index {
+                SourceIndex::Source(idx) => maps.get(&idx).and_then(|m| m.find_line(i.start)).map(|line| (idx, line)),
+                SourceIndex::NoSource => None
+            })
+            .collect();
         debug!("Instruction length: {}", source_map.len());
         let mut neg_one_len = 0;
         source_map.iter().for_each(|x| {
@@ -33,12 +50,36 @@ impl SoliditySourceMap {
         });
 
         Self {
-            map: Map::new(src),
+            file,
+            maps,
             program_map: source_map,
-            line_cache: cache
+            line_cache: cache,
+            lines,
         }
     }
 
+    // the file an instruction maps to. Code the compiler generates is attributed to the contract's file
+    fn index(inst: &Instruction, file: usize) -> usize {
+        match inst.source_index {
+            SourceIndex::Source(idx) => idx,
+            SourceIndex::NoSource => file
+        }
+    }
+
+    // map of the contract's own file
+    fn map(&self) -> &Map {
+        self.maps.get(&self.file).expect("the contract's own source is always mapped; qed")
+    }
+
+    // map of the file an instruction maps to
+    fn map_for(&self, inst: &Instruction) -> &Map {
+        self.maps.get(&Self::index(inst, self.file)).unwrap_or_else(|| self.map())
+    }
+
+    fn instruction(&self, offset: OpcodeOffset) -> Result<&Instruction, Error> {
+        Ok(self.program_map.get(offset).ok_or(SolidityError::SourceMap(SourceMapError::PositionNotFound))?)
+    }
+
     // find the instructions with shortest length, and returns the line number that contains that
     // offset
     fn shortest_len<'a>(prog_map: &'a Vec<Instruction>, map: &Map, lineno: usize) -> Option<&'a Instruction> {
@@ -77,10 +118,10 @@ impl SourceMap for SoliditySourceMap {
         if from > self.program_map.len() {
             return Err(SolidityError::SourceMap(SourceMapError::PositionOutOfBounds)).map_err(|e| e.into());
         }
-        let (lineStart, lineEnd) = self.map.range(LineNumber::Range(lineno))?;
+        let (lineStart, lineEnd) = self.map().range(LineNumber::Range(lineno))?;
 
-        for inst in &self.program_map[from..] {
-            if self.map.find_line(inst.start).expect("line in program map should always be found in source code; qed") == lineno {
+        for inst in self.program_map[from..].iter().filter(|i| Self::index(i, self.file) == self.file) {
+            if self.map().find_line(inst.start).expect("line in program map should always be found in source code; qed") == lineno {
                 trace!("Instruction: {}", inst);
                 return Ok(inst.position);
             }
@@ -89,28 +130,28 @@ impl SourceMap for SoliditySourceMap {
     }
 
     fn char_pos_from_lineno(&self, lineno: LineNo) -> Result<CharOffset, Error> {
-        Ok(self.map.find_offset(LineNumber::NoLeadingWhitespace(lineno))?)
+        Ok(self.map().find_offset(LineNumber::NoLeadingWhitespace(lineno))?)
     }
 
     fn lineno_from_char_pos(&self, offset: CharOffset) -> Result<LineNo, Error> {
-        Ok(self.map.find_line(offset).ok_or(SolidityError::SourceMap(SourceMapError::LineNotFound))?)
+        Ok(self.map().find_line(offset).ok_or(SolidityError::SourceMap(SourceMapError::LineNotFound))?)
     }
 
     fn lineno_from_opcode_pos(&self, offset: OpcodeOffset) -> Result<LineNo, Error> {
-        let pos = self.program_map.get(offset).ok_or(SolidityError::SourceMap(SourceMapError::PositionNotFound))?;
-        Ok(self.map.find_line(pos.start).ok_or(SolidityError::SourceMap(SourceMapError::LineNotFound))?)
+        let pos = self.instruction(offset)?;
+        Ok(self.map_for(pos).find_line(pos.start).ok_or(SolidityError::SourceMap(SourceMapError::LineNotFound))?)
     }
     
     /// finds the current range from opcode offset
     fn current_range(&self, offset: OpcodeOffset) -> Result<String, Error> {
-        let pos = self.program_map.get(offset).ok_or(SolidityError::SourceMap(SourceMapError::PositionNotFound))?;
-        Ok(self.map.raw_range(&pos.start, &pos.length)?)
+        let pos = self.instruction(offset)?;
+        Ok(self.map_for(pos).raw_range(&pos.start, &pos.length)?)
     }
 
     /// Finds the current line from the an opcode offset
     fn current_line(&self, offset: OpcodeOffset) -> Result<Line, Error> {
         let line = self.lineno_from_opcode_pos(offset)?;
-        let line_str = self.map.line(line)?;
+        let line_str = self.map_for(self.instruction(offset)?).line(line)?;
         let pos = self.program_map.get(offset).ok_or(SolidityError::SourceMap(SourceMapError::PositionNotFound))?;
         // trace!("Real line: \n{}\n", self.map.line_from_range((pos.start, pos.start + pos.length)).unwrap());
         Ok((line, String::from_iter(line_str)))
@@ -122,7 +163,7 @@ impl SourceMap for SoliditySourceMap {
         if count > line {
             return Err(SolidityError::SourceMap(SourceMapError::CountOutOfBounds)).map_err(|e| e.into());
         }
-        Ok(self.map
+        Ok(self.map_for(self.instruction(offset)?)
            .lines((line - count)..line)?
            .into_iter()
            .enumerate()
@@ -132,16 +173,46 @@ impl SourceMap for SoliditySourceMap {
     /// Finds the next `count` lines from a bytecode offset
     fn next_lines(&self, offset: OpcodeOffset, count: usize) -> Result<Vec<Line>, Error> {
         let line = self.lineno_from_opcode_pos(offset)?;
-        if count > (self.map.len() - line) {
+        let map = self.map_for(self.instruction(offset)?);
+        if count > (map.len() - line) {
             return Err(SolidityError::SourceMap(SourceMapError::CountOutOfBounds)).map_err(|e| e.into());
         }
 
-        Ok(self.map
+        Ok(map
            .lines(line..(line + count))?
            .into_iter()
            .enumerate()
            .collect::<Vec<Line>>())
     }
+
+    fn source_range(&self, offset: OpcodeOffset) -> Result<SourceRange, Error> {
+        let pos = self.program_map.get(offset).ok_or(SolidityError::SourceMap(SourceMapError::PositionNotFound))?;
+        Ok((pos.start, pos.start + pos.length))
+    }
+
+    fn source_index(&self, offset: OpcodeOffset) -> Result<usize, Error> {
+        Ok(Self::index(self.instruction(offset)?, self.file))
+    }
+
+    fn has_line(&self, file: usize, lineno: LineNo) -> bool {
+        self.lines.contains(&(file, lineno))
+    }
+
+    fn has_source(&self, offset: OpcodeOffset) -> bool {
+        match self.program_map.get(offset).map(|i| &i.source_index) {
+            Some(SourceIndex::Source(_)) => true,
+            _ => false
+        }
+    }
+
+    fn jump(&self, offset: OpcodeOffset) -> Result<JumpKind, Error> {
+        let pos = self.program_map.get(offset).ok_or(SolidityError::SourceMap(SourceMapError::PositionNotFound))?;
+        Ok(match pos.jump {
+            Jump::IntoFunc => JumpKind::IntoFunction,
+            Jump::ReturnFunc => JumpKind::ReturnFromFunction,
+            Jump::NormJump => JumpKind::Regular,
+        })
+    }
 }
 
 
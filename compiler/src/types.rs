@@ -1,6 +1,10 @@
 use serde_derive::*;
+use std::str::FromStr;
+use failure::Error;
+use super::err::LanguageError;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// The Ethereum hard fork that code is compiled for and executed with
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all="lowercase")]
 pub enum FoundationVersion {
     Byzantium,
@@ -27,6 +31,34 @@ impl From<FoundationVersion> for String {
     }
 }
 
+impl FromStr for FoundationVersion {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<FoundationVersion, Error> {
+        let s = s.to_ascii_lowercase();
+        match s.as_str() {
+            "byzantium" => Ok(FoundationVersion::Byzantium),
+            "frontier" => Ok(FoundationVersion::Frontier),
+            "homestead" => Ok(FoundationVersion::Homestead),
+            "spuriousdragon" => Ok(FoundationVersion::SpuriousDragon),
+            _ => Err(LanguageError::UnknownFork(s).into())
+        }
+    }
+}
+
+impl From<FoundationVersion> for solc_api::types::FoundationVersion {
+    fn from(ver: FoundationVersion) -> solc_api::types::FoundationVersion {
+        match ver {
+            FoundationVersion::Byzantium => solc_api::types::FoundationVersion::Byzantium,
+            // Solc has no Frontier target, so code is compiled for Homestead. Homestead added
+            // DELEGATECALL, which solc emits for calls to libraries: contracts that use libraries
+            // fail when emulated with Frontier rules
+            FoundationVersion::Frontier => solc_api::types::FoundationVersion::Homestead,
+            FoundationVersion::Homestead => solc_api::types::FoundationVersion::Homestead,
+            FoundationVersion::SpuriousDragon => solc_api::types::FoundationVersion::SpuriousDragon,
+        }
+    }
+}
+
 //TODO: USE THIS ENUM!
 /// Language Enum
 #[derive(Serialize, Deserialize, Debug, Clone)]
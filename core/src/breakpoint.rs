@@ -0,0 +1,210 @@
+//! Breakpoints, and deciding whether execution stops at them
+use std::fmt;
+use failure::Error;
+use ethereum_types::{Address, U256};
+use sputnikvm::Opcode;
+use edb_emul::{disasm, Write};
+use super::err::DebugError;
+
+/// Where a breakpoint stops execution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// a line of a source file
+    Line(String, usize),
+    /// a byte offset in the code of whichever contract is executing
+    Pc(usize),
+    /// every instruction of a class of opcodes, ie `SSTORE` or `LOG`
+    Opcode(String),
+    /// every write to `slots` slots of the storage of `address`, starting at `slot`.
+    /// `name` is what was watched, ie a variable
+    Storage { address: Address, slot: U256, slots: usize, name: String },
+    /// every write to `len` bytes of memory from `offset`, in the frame at call depth `depth`
+    Memory { depth: usize, offset: usize, len: usize },
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Line(file, line) => write!(f, "{}:{}", file, line),
+            Target::Pc(pc) => write!(f, "*{:#x}", pc),
+            Target::Opcode(class) => write!(f, "{}", class),
+            Target::Storage { name, .. } => write!(f, "storage {}", name),
+            Target::Memory { offset, len, .. } => write!(f, "memory {:#x}..{:#x}", offset, offset + len),
+        }
+    }
+}
+
+/// Where execution is, to check breakpoints against
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Location {
+    /// file and line of the next instruction, only if execution has just arrived at the line
+    pub line: Option<(String, usize)>,
+    /// byte offset of the next instruction
+    pub pc: Option<usize>,
+    pub opcode: Option<Opcode>,
+    /// writes made by the instruction executed last
+    pub writes: Vec<Write>,
+}
+
+/// A write that stopped execution at a watchpoint
+#[derive(Debug, Clone)]
+pub struct WatchHit {
+    /// id of the watchpoint
+    pub id: usize,
+    pub write: Write,
+    /// file and line of the instruction that made the write
+    pub line: Option<(String, usize)>,
+}
+
+/// A place in the code that execution stops at
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    /// number the breakpoint is referred to by
+    pub id: usize,
+    pub target: Target,
+    /// contracts with code at the line
+    pub contracts: Vec<String>,
+    /// expression that must hold for execution to stop
+    pub condition: Option<String>,
+    /// times execution has reached the breakpoint while its condition held
+    pub hits: usize,
+    /// how many more hits pass without stopping
+    pub ignore: usize,
+    pub enabled: bool,
+}
+
+impl Breakpoint {
+    /// Whether the breakpoint is at `location`, and can stop execution
+    pub(crate) fn is_at(&self, location: &Location) -> bool {
+        self.enabled && match &self.target {
+            Target::Line(file, line) => location.line.as_ref().map(|(f, l)| f == file && l == line).unwrap_or(false),
+            Target::Pc(pc) => location.pc == Some(*pc),
+            Target::Opcode(class) => location.opcode.map(|op| disasm::in_class(op, class)).unwrap_or(false),
+            Target::Storage { .. } | Target::Memory { .. } => location.writes.iter().any(|w| self.watches(w)),
+        }
+    }
+
+    /// Whether the breakpoint is a watchpoint on the location `write` writes to
+    pub fn watches(&self, write: &Write) -> bool {
+        match (&self.target, write) {
+            (Target::Storage { address, slot, slots, .. }, Write::Storage { address: a, slot: s, .. }) => {
+                let s = U256(s.0);
+                ethereum_types::H160(a.0) == *address && s >= *slot && s - *slot < U256::from(*slots)
+            },
+            (Target::Memory { depth, offset, len }, Write::Memory { depth: d, offset: o, new, .. }) => {
+                depth == d && *o < offset + len && *offset < o + new.len()
+            },
+            _ => false
+        }
+    }
+
+    /// Count a hit, returning whether it stops execution
+    pub(crate) fn hit(&mut self) -> bool {
+        self.hits += 1;
+        if self.ignore > 0 {
+            self.ignore -= 1;
+            false
+        } else {
+            true
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:<4} {:<8} {}", self.id, if self.enabled { "y" } else { "n" }, self.target)?;
+        write!(f, "  hit {} time{}", self.hits, if self.hits == 1 { "" } else { "s" })?;
+        if self.ignore > 0 {
+            write!(f, ", ignoring the next {}", self.ignore)?;
+        }
+        if !self.contracts.is_empty() {
+            write!(f, "  in {}", self.contracts.join(", "))?;
+        }
+        if let Some(condition) = &self.condition {
+            write!(f, "  if {}", condition)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every breakpoint that has been set, in the order they were set
+#[derive(Debug, Clone, Default)]
+pub struct Breakpoints {
+    list: Vec<Breakpoint>,
+    next_id: usize,
+}
+
+impl Breakpoints {
+    /// Add a breakpoint, returning its id.
+    /// Setting a breakpoint where one already is replaces its condition
+    pub fn insert(&mut self, target: Target, condition: Option<String>, contracts: Vec<String>) -> usize {
+        if let Some(existing) = self.list.iter_mut().find(|b| b.target == target) {
+            existing.condition = condition;
+            existing.contracts = contracts;
+            return existing.id;
+        }
+        self.next_id += 1;
+        self.list.push(Breakpoint { id: self.next_id, target, contracts, condition, hits: 0, ignore: 0, enabled: true });
+        self.next_id
+    }
+
+    pub fn remove(&mut self, id: usize) -> Result<Breakpoint, Error> {
+        let pos = self.list.iter().position(|b| b.id == id).ok_or(DebugError::NoBreakpoint(id))?;
+        Ok(self.list.remove(pos))
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Result<&mut Breakpoint, Error> {
+        Ok(self.list.iter_mut().find(|b| b.id == id).ok_or(DebugError::NoBreakpoint(id))?)
+    }
+
+    /// Whether any breakpoint can stop execution
+    pub fn any_enabled(&self) -> bool {
+        self.list.iter().any(|b| b.enabled)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.list.iter()
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut Breakpoint> {
+        self.list.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculate::speculate;
+
+    speculate! {
+        it "keeps breakpoints after they are hit" {
+            let mut breakpoints = Breakpoints::default();
+            let id = breakpoints.insert(Target::Line("Loop.sol".to_string(), 7), None, vec!["Loop".to_string()]);
+            breakpoints.get_mut(id).unwrap().ignore = 1;
+            let location = Location { line: Some(("Loop.sol".to_string(), 7)), ..Location::default() };
+            let stops = (0..3)
+                .map(|_| breakpoints.iter_mut().filter(|b| b.is_at(&location)).all(|b| b.hit()))
+                .collect::<Vec<_>>();
+            assert_eq!(stops, vec![false, true, true]);
+            assert_eq!(breakpoints.iter().next().unwrap().hits, 3);
+        }
+
+        it "stops at every opcode of a class" {
+            let mut breakpoints = Breakpoints::default();
+            breakpoints.insert(Target::Opcode("LOG".to_string()), None, Vec::new());
+            let at = |opcode| Location { opcode: Some(opcode), ..Location::default() };
+            assert!(breakpoints.iter().all(|b| b.is_at(&at(Opcode::LOG(2)))));
+            assert!(!breakpoints.iter().any(|b| b.is_at(&at(Opcode::SSTORE))));
+        }
+
+        it "watches writes that overlap memory" {
+            let mut breakpoints = Breakpoints::default();
+            breakpoints.insert(Target::Memory { depth: 0, offset: 0x40, len: 32 }, None, Vec::new());
+            let write = |depth, offset| Write::Memory { depth, offset, old: vec![0; 32], new: vec![1; 32] };
+            let bp = breakpoints.iter().next().unwrap();
+            assert!(bp.watches(&write(0, 0x30)));
+            assert!(!bp.watches(&write(0, 0x60)));
+            assert!(!bp.watches(&write(1, 0x40)));
+        }
+    }
+}
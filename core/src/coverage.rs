@@ -0,0 +1,103 @@
+//! Which instructions and lines of source have been executed, for measuring code coverage
+use std::{path::Path, rc::Rc, fmt::Write, collections::{HashMap, BTreeMap}};
+use edb_compiler::{CodeFile, CodeKind, ContractFile};
+use edb_emul::disasm;
+
+/// Instructions of the contracts that have been executed while debugging, and how many times.
+/// Every instruction stepped counts, in every transaction the debugger runs
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    executed: HashMap<String, HashMap<(CodeKind, usize), usize>>,
+}
+
+impl Coverage {
+    /// Count an execution of the instruction at `position` of the code of `contract`
+    pub(crate) fn record(&mut self, contract: &str, kind: CodeKind, position: usize) {
+        *self.executed
+            .entry(contract.to_string())
+            .or_insert_with(HashMap::new)
+            .entry((kind, position))
+            .or_insert(0) += 1;
+    }
+
+    /// Undo an execution counted by `record`, for an instruction that has been stepped back over
+    pub(crate) fn forget(&mut self, contract: &str, kind: CodeKind, position: usize) {
+        if let Some(hits) = self.executed.get_mut(contract).and_then(|e| e.get_mut(&(kind, position))) {
+            *hits = hits.saturating_sub(1);
+        }
+    }
+
+    /// Times the instruction at `position` of the code of `contract` has been executed
+    pub fn hits(&self, contract: &str, kind: CodeKind, position: usize) -> usize {
+        self.executed.get(contract).and_then(|e| e.get(&(kind, position))).cloned().unwrap_or(0)
+    }
+
+    /// Every line of source that the code of the contracts in `file` is compiled from, with the
+    /// times it has been executed: as many as the instruction of the line executed most.
+    /// Lines are zero-indexed, and grouped by the file they are in
+    pub fn lines(&self, file: &CodeFile) -> Vec<(Rc<ContractFile>, BTreeMap<usize, usize>)> {
+        let mut files: BTreeMap<usize, BTreeMap<usize, usize>> = BTreeMap::new();
+        for contract in file.contracts().iter() {
+            let codes = [(CodeKind::Creation, contract.creation_bytecode()), (CodeKind::Runtime, contract.runtime_bytecode())];
+            for (kind, code) in codes.iter() {
+                let map = contract.source_map_for(*kind);
+                for position in disasm::instructions(code).map(|i| i.position).filter(|p| map.has_source(*p)) {
+                    let (id, line) = match (map.source_index(position), map.lineno_from_opcode_pos(position)) {
+                        (Ok(id), Ok(line)) => (id, line),
+                        _ => continue
+                    };
+                    let hits = files.entry(id).or_insert_with(BTreeMap::new).entry(line).or_insert(0);
+                    *hits = (*hits).max(self.hits(contract.name(), *kind, position));
+                }
+            }
+        }
+        files.into_iter().filter_map(|(id, lines)| file.file_by_id(id).map(|f| (f, lines))).collect()
+    }
+
+    /// Coverage of every file with code in `file` as an LCOV tracefile, with one record per file
+    pub fn lcov(&self, file: &CodeFile) -> String {
+        self.lines(file).iter().map(|(source, lines)| lcov_record(source.file_path(), lines)).collect()
+    }
+}
+
+// the LCOV record of one source file. LCOV numbers lines from one
+fn lcov_record(path: &Path, lines: &BTreeMap<usize, usize>) -> String {
+    let mut record = format!("TN:\nSF:{}\n", path.display());
+    for (line, hits) in lines.iter() {
+        writeln!(record, "DA:{},{}", line + 1, hits).expect("Writing to a String cannot fail; qed");
+    }
+    writeln!(record, "LF:{}\nLH:{}\nend_of_record", lines.len(), lines.values().filter(|h| **h > 0).count()).expect("Writing to a String cannot fail; qed");
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculate::speculate;
+
+    speculate! {
+        it "writes an lcov record" {
+            let lines = vec![(4, 2), (5, 0), (9, 1)].into_iter().collect::<BTreeMap<_, _>>();
+            let record = lcov_record(Path::new("contracts/Loop.sol"), &lines);
+            assert_eq!(record, "TN:\nSF:contracts/Loop.sol\nDA:5,2\nDA:6,0\nDA:10,1\nLF:3\nLH:2\nend_of_record\n");
+        }
+
+        it "counts executions of instructions" {
+            let mut coverage = Coverage::default();
+            coverage.record("Loop", CodeKind::Runtime, 7);
+            coverage.record("Loop", CodeKind::Runtime, 7);
+            assert_eq!(coverage.hits("Loop", CodeKind::Runtime, 7), 2);
+            assert_eq!(coverage.hits("Loop", CodeKind::Creation, 7), 0);
+        }
+
+        it "forgets executions that are stepped back over" {
+            let mut coverage = Coverage::default();
+            coverage.record("Loop", CodeKind::Runtime, 7);
+            coverage.record("Loop", CodeKind::Runtime, 7);
+            coverage.forget("Loop", CodeKind::Runtime, 7);
+            assert_eq!(coverage.hits("Loop", CodeKind::Runtime, 7), 1);
+            coverage.forget("Loop", CodeKind::Runtime, 9);
+            assert_eq!(coverage.hits("Loop", CodeKind::Runtime, 9), 0);
+        }
+    }
+}
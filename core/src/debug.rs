@@ -2,42 +2,265 @@
 
 use std::{
     path::PathBuf,
-    collections::HashMap
+    collections::HashMap,
+    cell::RefCell,
+    rc::Rc,
 };
 
 use failure::Error;
 use log::*;
 use sputnikvm::Memory;
+use web3::types::{BlockId, H256};
 
-use edb_compiler::{CodeFile, AbstractFunction, CompiledFiles};
-use edb_emul::{emulator::{Emulator, Action}, ValidTransaction, HeaderParams};
-use super::err::EvmError;
+use edb_compiler::{CodeFile, CompiledFiles, CodeKind, Contract, ContractFile, SourceMap, Line, JumpKind, Declaration, VarType};
+use edb_emul::{emulator::{Emulator, Action}, disasm::{self, Instruction}, ValidTransaction, HeaderParams, Patch, Frame, Write, Failure, EventLog, Trace, StateOverride};
+use super::err::{EvmError, DebugError};
+use super::value::Value;
+use super::storage::{Layout, Position};
+use super::eval::{Expr, Scope};
+use super::breakpoint::{Breakpoint, Breakpoints, Target, Location, WatchHit};
+use super::diagnosis::{self, FailureReport};
+use super::events::{self, DecodedLog};
+use super::profile::{Meter, Profile, LineGas, FunctionGas};
+use super::coverage::Coverage;
 
-pub struct Debugger<T> where T: web3::Transport {
+pub struct Debugger<T, P> where T: web3::Transport, P: Patch {
     file: CodeFile,
-    emul: Emulator<T>,
-    breakpoints: Vec<Breakpoint>,
-    curr_name: String,
+    emul: Emulator<T, P>,
+    breakpoints: Breakpoints,
+    /// writes made by the instruction executed last
+    writes: Rc<RefCell<Vec<Write>>>,
+    /// the writes that stopped execution at a watchpoint
+    watch_hits: Vec<WatchHit>,
+    /// the failure that stopped execution
+    failure: Option<FailureReport>,
+    /// the contract the transaction is sent to
+    root_name: String,
+    /// the internal function calls in each frame
+    calls: Vec<Vec<InternalCall>>,
+    /// the contract each frame executes, found when the frame was entered
+    contracts: Vec<FrameContract>,
+    /// every instruction stepped, to undo when stepping back
+    stepped: Vec<Stepped>,
+    /// instructions executed, if coverage is being recorded
+    coverage: Option<Coverage>,
 }
 
-pub type Breakpoint = usize;
+/// What stepping an instruction changed in the debugger
+#[derive(Debug, Clone)]
+struct Stepped {
+    /// how the instruction changed the internal function calls, if it did
+    calls: Option<CallChange>,
+    /// the instruction, if coverage counted it
+    covered: Option<(String, CodeKind, usize)>,
+}
+
+/// A change to the internal function calls, kept to undo it
+#[derive(Debug, Clone)]
+enum CallChange {
+    /// a function was entered
+    Entered,
+    /// a function returned
+    Returned(InternalCall),
+    /// frames were entered or left. Holds how many frames there were before, and the internal
+    /// calls of the frames that were left
+    Frames(usize, Vec<Vec<InternalCall>>),
+    /// the transaction ran without stepping. Holds every internal call from before
+    Ran(Vec<Vec<InternalCall>>),
+}
+
+impl CallChange {
+    /// Restore `calls` to what they were before the change
+    fn undo(self, calls: &mut Vec<Vec<InternalCall>>) {
+        match self {
+            CallChange::Entered => { calls.last_mut().and_then(|c| c.pop()); },
+            CallChange::Returned(call) => if let Some(c) = calls.last_mut() {
+                c.push(call);
+            },
+            CallChange::Frames(len, left) => {
+                calls.truncate(len);
+                calls.extend(left);
+            },
+            CallChange::Ran(before) => *calls = before,
+        }
+    }
+}
+
+/// The contract a call frame executes
+#[derive(Debug, Clone)]
+struct FrameContract {
+    /// code of the frame the contract was found for
+    code: Rc<Vec<u8>>,
+    /// index of the contract, and which of its bytecode runs. `None` if no contract has the code
+    contract: Option<(usize, CodeKind)>,
+}
 
-impl<T> Debugger<T> where T: web3::Transport {
+/// A jump into a function within the same contract
+#[derive(Debug, Clone)]
+struct InternalCall {
+    /// instruction position of the jump
+    call_site: usize,
+    /// height of the stack once the function has been entered
+    stack_height: usize,
+}
+
+/// An instruction of the code that is executing, and the source it was compiled from
+#[derive(Debug, Clone)]
+pub struct Disassembled {
+    pub instruction: Instruction,
+    /// whether this is the next instruction to be executed
+    pub current: bool,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    /// source code the instruction maps to
+    pub source: Option<String>,
+}
+
+/// One function on the call stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    /// address the executing code was loaded from
+    pub address: ethereum_types::Address,
+    /// contract executing. `None` if no source is known for the code
+    pub contract: Option<String>,
+    /// function executing
+    pub function: Option<String>,
+    /// source file of the contract
+    pub file: Option<String>,
+    /// line the function is currently at
+    pub line: Option<usize>,
+}
 
+impl<T, P> Debugger<T, P> where T: web3::Transport, P: Patch {
+
+    /// Create a new debugger for transaction `tx`, executing against the state of block `state`
     pub fn new(path: PathBuf,
                files: CompiledFiles,
                client: web3::Web3<T>,
                tx: ValidTransaction,
-               block: HeaderParams,
+               state: BlockId,
                contract_name: &str
                 )
         -> Result<Self, Error>
     {
+        let emul = Emulator::new(tx, state, client)?;
+        Self::with_emulator(path, files, emul, contract_name)
+    }
+
+    /// Create a new debugger for the mined transaction `hash`, replaying it against the state it
+    /// was originally executed with
+    pub fn replay(path: PathBuf,
+                  files: CompiledFiles,
+                  client: web3::Web3<T>,
+                  hash: H256,
+                  contract_name: &str
+                  )
+        -> Result<Self, Error>
+    {
+        let emul = Emulator::replay(hash, client)?;
+        Self::with_emulator(path, files, emul, contract_name)
+    }
+
+    fn with_emulator(path: PathBuf, files: CompiledFiles, mut emul: Emulator<T, P>, contract_name: &str) -> Result<Self, Error> {
         let file = CodeFile::new(files, path)?;
-        let emul = Emulator::new(tx, block, client);
-        let breakpoints = Vec::new();
-        let curr_name = String::from(contract_name);
-        Ok(Self {file, emul, breakpoints, curr_name})
+        let breakpoints = Breakpoints::default();
+        let root_name = String::from(contract_name);
+        let writes = Rc::new(RefCell::new(Vec::new()));
+        let observed = writes.clone();
+        emul.on_write(move |write| observed.borrow_mut().push(write.clone()));
+        Ok(Self {file, emul, breakpoints, writes, watch_hits: Vec::new(), failure: None, root_name, calls: Vec::new(), contracts: Vec::new(), stepped: Vec::new(), coverage: None})
+    }
+
+    fn root_kind(&self) -> CodeKind {
+        if self.emul.is_creation() {
+            CodeKind::Creation
+        } else {
+            CodeKind::Runtime
+        }
+    }
+
+    /// The contract executing in the current frame, and which of its bytecode is running.
+    /// Contracts are matched by their bytecode. If nothing matches, the outermost frame is assumed
+    /// to be the contract the transaction was sent to. `None` if no source is known for the code
+    fn current_contract(&self) -> Option<(&Contract, CodeKind)> {
+        match self.contracts.len() {
+            0 => self.root_contract(),
+            frames => self.frame_contract(frames - 1)
+        }
+    }
+
+    // the contract the frame at `depth` executes, as found when it was entered
+    fn frame_contract(&self, depth: usize) -> Option<(&Contract, CodeKind)> {
+        let (index, kind) = self.contracts.get(depth)?.contract?;
+        Some((&self.file.contracts()[index], kind))
+    }
+
+    fn contract_for(&self, frame: &Frame) -> Option<(usize, CodeKind)> {
+        self.file.contracts()
+            .iter()
+            .enumerate()
+            .find_map(|(i, c)| c.code_kind(frame.code.as_slice()).map(|kind| (i, kind)))
+            .or_else(|| if frame.call_site.is_some() {
+                None
+            } else {
+                self.file.contracts().iter().position(|c| c.name() == self.root_name).map(|i| (i, self.root_kind()))
+            })
+    }
+
+    // find the contracts of the frames entered since the last call, and forget those of the
+    // frames that were left
+    fn update_contracts(&mut self) {
+        let frames = self.emul.frames();
+        let kept = self.contracts.iter()
+            .zip(frames.iter())
+            .take_while(|(c, frame)| Rc::ptr_eq(&c.code, &frame.code))
+            .count();
+        let entered = frames[kept..].iter()
+            .map(|f| FrameContract { code: f.code.clone(), contract: self.contract_for(f) })
+            .collect::<Vec<_>>();
+        self.contracts.truncate(kept);
+        self.contracts.extend(entered);
+    }
+
+    fn root_contract(&self) -> Option<(&Contract, CodeKind)> {
+        self.file.contract(self.root_name.as_str()).ok().map(|c| (c, self.root_kind()))
+    }
+
+    /// the source map for the code that is currently executing
+    fn source_map(&self) -> Result<Rc<dyn SourceMap>, Error> {
+        let (contract, kind) = self.current_contract().ok_or_else(|| DebugError::NoSource(self.code_address()))?;
+        Ok(contract.source_map_for(kind))
+    }
+
+    // address the code executing in the current frame was loaded from
+    fn code_address(&self) -> bigint::H160 {
+        self.emul.frame().map(|f| f.code_address).unwrap_or_else(|| self.emul.resident_address())
+    }
+
+    /// the next instruction, for code that has no source
+    fn opcode_line(&self) -> Result<Line, Error> {
+        Ok((self.emul.offset()?, disasm::name(self.emul.opcode()?)))
+    }
+
+    /// Name of the contract executing in the current frame. `None` if no source is known for it
+    pub fn current_contract_name(&self) -> Option<&str> {
+        self.current_contract().map(|(c, _)| c.name())
+    }
+
+    /// The call frames of the transaction, outermost first
+    pub fn frames(&self) -> &[Frame] {
+        self.emul.frames()
+    }
+
+    /// The call depth of the current frame. `None` if execution has not started
+    pub fn depth(&self) -> Option<usize> {
+        self.emul.depth()
+    }
+
+    /// Address of the contract being debugged.
+    /// If the transaction creates a contract, this is the address it will be deployed to
+    pub fn address(&self) -> ethereum_types::Address {
+        ethereum_types::H160(self.emul.resident_address().0)
     }
 
     // TODO finish
@@ -47,49 +270,284 @@ impl<T> Debugger<T> where T: web3::Transport {
     }
     */
 
-    /// Begins the program, and runs until it hits a breakpoint
+    /// Begins the program, and runs until it hits a breakpoint or a frame fails
     pub fn run(&mut self) -> Result<(), Error> {
-        self.emul.fire(Action::StepForward)?;
-        self.breakpoints.reverse();
-        if let Some(b) = self.breakpoints.pop() {
-            self.step_loop(|line| *line == b)?;
-            Ok(())
-        } else { // if no breakpoints, just execute the contract
-            self.emul.fire(Action::Exec)?;
-            Ok(())
+        self.failure = None;
+        let previous = self.location();
+        self.step_instruction()?;
+        let location = self.location();
+        if self.hit_breakpoint(&previous, &location)? {
+            return Ok(());
         }
+        self.next()
     }
 
     /// Runs the transaction to the end, ignoring any breakpoints.
     pub fn run_to_end(&mut self) -> Result<(), Error> {
+        // only instructions that are stepped are recorded
+        if self.coverage.is_some() {
+            while !self.emul.finished() {
+                self.step_instruction()?;
+            }
+            return Ok(());
+        }
+        // stepping back from the end undoes the instruction stepped last, and the calls from before
+        // it are restored with it
+        let mut before = self.calls.clone();
+        let covered = match self.stepped.pop() {
+            Some(stepped) => {
+                if let Some(change) = stepped.calls {
+                    change.undo(&mut before);
+                }
+                stepped.covered
+            },
+            None => None
+        };
+        self.stepped.push(Stepped { calls: Some(CallChange::Ran(before)), covered });
         self.emul.fire(Action::Exec)?;
+        self.update_contracts();
+        // every function has returned once the transaction is over
+        self.calls = vec![Vec::new(); self.emul.frames().len()];
         Ok(())
     }
 
-    /// Sets a breakpoint at a line number
-    pub fn set_breakpoint(&mut self, line: Breakpoint) -> Result<(), Error> {
-        if self.file.unique_exists(line, self.curr_name.as_str())? {
-            match self.breakpoints.binary_search(&line) {
-                Ok(_) => {} // already inserted
-                Err(pos) => self.breakpoints.insert(pos, line)
-            };
+    /// Undoes the instruction executed last
+    pub fn step_back(&mut self) -> Result<(), Error> {
+        self.writes.borrow_mut().clear();
+        self.failure = None;
+        self.emul.fire(Action::StepBack)?;
+        self.update_contracts();
+        if let Some(stepped) = self.stepped.pop() {
+            if let Some(change) = stepped.calls {
+                change.undo(&mut self.calls);
+            }
+            if let (Some(coverage), Some((name, kind, pos))) = (self.coverage.as_mut(), stepped.covered) {
+                coverage.forget(&name, kind, pos);
+            }
         }
         Ok(())
     }
 
+    /// Sets a breakpoint at a line of `file`, or of the file execution is currently in if no file
+    /// is given. The breakpoint stops execution in any contract whose code maps to the line,
+    /// including libraries and contracts that inherit the code from a base contract in another file.
+    /// Execution only stops at the breakpoint if `condition` holds.
+    /// Returns the id of the breakpoint
+    pub fn set_breakpoint(&mut self, file: Option<&str>, line: usize, condition: Option<&str>) -> Result<usize, Error> {
+        let source = match file {
+            Some(f) => self.file.file_by_name(f)
+                .ok_or_else(|| DebugError::InvalidBreakpoint(format!("{}: no such source file", f)))?,
+            None => self.current_file()
+                .or_else(|| self.root_contract().map(|(c, _)| c.file()))
+                .ok_or_else(|| DebugError::InvalidBreakpoint(format!("line {}: no source is known for the current contract", line)))?
+        };
+        // a line has code if any contract maps an instruction to it, whichever file the contract is in
+        let contracts = self.file.contracts()
+            .iter()
+            .filter(|c| {
+                c.source_map_for(CodeKind::Runtime).has_line(source.id(), line)
+                    || c.source_map_for(CodeKind::Creation).has_line(source.id(), line)
+            })
+            .map(|c| c.name().to_string())
+            .collect::<Vec<_>>();
+        if contracts.is_empty() {
+            return Err(DebugError::InvalidBreakpoint(format!("{}:{} has no code", source.file_name(), line)).into());
+        }
+        let target = Target::Line(source.file_name().to_string(), line);
+        self.add_breakpoint(target, condition, contracts)
+    }
+
+    /// Sets a breakpoint at byte offset `pc` of the code that is executing, in any contract.
+    /// Returns the id of the breakpoint
+    pub fn set_pc_breakpoint(&mut self, pc: usize, condition: Option<&str>) -> Result<usize, Error> {
+        self.add_breakpoint(Target::Pc(pc), condition, Vec::new())
+    }
+
+    /// Sets a breakpoint at every instruction of a class of opcodes: either one opcode, ie `SSTORE`,
+    /// or a family of them, ie `LOG`. Returns the id of the breakpoint
+    pub fn set_opcode_breakpoint(&mut self, class: &str, condition: Option<&str>) -> Result<usize, Error> {
+        if !disasm::is_class(class) {
+            return Err(DebugError::InvalidBreakpoint(format!("`{}` is not an opcode", class)).into());
+        }
+        self.add_breakpoint(Target::Opcode(class.to_ascii_uppercase()), condition, Vec::new())
+    }
+
+    fn add_breakpoint(&mut self, target: Target, condition: Option<&str>, contracts: Vec<String>) -> Result<usize, Error> {
+        if let Some(c) = condition {
+            Expr::parse(c)?;
+        }
+        Ok(self.breakpoints.insert(target, condition.map(|c| c.to_string()), contracts))
+    }
+
     /// Removes a breakpoint
-    pub fn remove_breakpoint(&mut self, line: Breakpoint) {
-        match self.breakpoints.binary_search(&line) {
-            Ok(pos) => {self.breakpoints.remove(pos); },
-            Err(_) => {} // element not in array
+    pub fn remove_breakpoint(&mut self, id: usize) -> Result<(), Error> {
+        self.breakpoints.remove(id)?;
+        Ok(())
+    }
+
+    /// Enables or disables a breakpoint. Disabled breakpoints never stop execution
+    pub fn enable_breakpoint(&mut self, id: usize, enabled: bool) -> Result<(), Error> {
+        self.breakpoints.get_mut(id)?.enabled = enabled;
+        Ok(())
+    }
+
+    /// Passes a breakpoint the next `count` times it is hit without stopping
+    pub fn ignore_breakpoint(&mut self, id: usize, count: usize) -> Result<(), Error> {
+        self.breakpoints.get_mut(id)?.ignore = count;
+        Ok(())
+    }
+
+    /// Every breakpoint, in the order they were set
+    pub fn breakpoints(&self) -> Vec<&Breakpoint> {
+        self.breakpoints.iter().collect()
+    }
+
+    /// Stops execution whenever an instruction writes to the storage `expr` is kept in. `expr` is a
+    /// slot number, or an expression that refers to storage, ie `balances[msg.sender]`.
+    /// Storage of the contract the current frame executes against is watched.
+    /// Returns the id of the watchpoint
+    pub fn watch_storage(&mut self, expr: &str, condition: Option<&str>) -> Result<usize, Error> {
+        let (slot, slots) = match Expr::parse(expr)? {
+            Expr::Literal(Value::Uint(slot)) => (slot, 1),
+            parsed => {
+                let (ty, position) = parsed.storage(&*self)?;
+                (position.slot, self.layout().slots(&ty))
+            }
+        };
+        let address = ethereum_types::H160(self.emul.frame().map(|f| f.address).unwrap_or_else(|| self.emul.resident_address()).0);
+        let target = Target::Storage { address, slot, slots, name: expr.to_string() };
+        self.add_breakpoint(target, condition, Vec::new())
+    }
+
+    /// Stops execution whenever an instruction writes to `len` bytes of memory from `offset`,
+    /// in the frame that is currently executing. Returns the id of the watchpoint
+    pub fn watch_memory(&mut self, offset: usize, len: usize, condition: Option<&str>) -> Result<usize, Error> {
+        if len == 0 {
+            return Err(DebugError::InvalidBreakpoint("an empty range of memory".to_string()).into());
+        }
+        let depth = self.emul.depth().unwrap_or(0);
+        self.add_breakpoint(Target::Memory { depth, offset, len }, condition, Vec::new())
+    }
+
+    /// The writes that stopped execution at a watchpoint, and the lines that made them.
+    /// Empty if execution did not stop at a watchpoint
+    pub fn watch_hits(&self) -> &[WatchHit] {
+        self.watch_hits.as_slice()
+    }
+
+    /// Ends the session, keeping its breakpoints to carry over to a new one
+    pub fn into_breakpoints(self) -> Breakpoints {
+        self.breakpoints
+    }
+
+    /// Replaces the breakpoints with those of an earlier session
+    pub fn restore_breakpoints(&mut self, breakpoints: Breakpoints) {
+        self.breakpoints = breakpoints;
+    }
+
+    /// Records every instruction executed from now on into `coverage`, including those of
+    /// transactions chained after this one
+    pub fn record_coverage(&mut self, coverage: Coverage) {
+        self.coverage = Some(coverage);
+    }
+
+    /// Stops recording coverage, returning what has been recorded
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    /// The instructions executed while coverage has been recorded. `None` if it is not being recorded
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// The coverage recorded as an LCOV tracefile, with a record for every file with code in the
+    /// contracts. `None` if coverage is not being recorded
+    pub fn lcov(&self) -> Option<String> {
+        self.coverage.as_ref().map(|c| c.lcov(&self.file))
+    }
+
+    // file and line of the next instruction. `None` if no source is known for it
+    fn location(&self) -> Option<(String, usize)> {
+        let (contract, kind) = self.current_contract()?;
+        let map = contract.source_map_for(kind);
+        let position = self.emul.instruction().ok()?;
+        let line = map.lineno_from_opcode_pos(position).ok()?;
+        Some((self.source_file(contract, &*map, position).file_name().to_string(), line))
+    }
+
+    // the source file of the next instruction. `None` if no source is known for it
+    fn current_file(&self) -> Option<Rc<ContractFile>> {
+        let (contract, kind) = self.current_contract()?;
+        let position = self.emul.instruction().ok()?;
+        Some(self.source_file(contract, &*contract.source_map_for(kind), position))
+    }
+
+    // The file an instruction of `contract` maps to. This is not the contract's own file if the
+    // instruction is from a library or base contract that is defined elsewhere
+    fn source_file(&self, contract: &Contract, map: &dyn SourceMap, position: usize) -> Rc<ContractFile> {
+        map.source_index(position)
+            .ok()
+            .and_then(|id| self.file.file_by_id(id))
+            .unwrap_or_else(|| contract.file())
+    }
+
+    // Whether a breakpoint at the next instruction, or a watchpoint on a write of the instruction
+    // executed last, stops execution. Counts a hit for every breakpoint there whose condition holds.
+    // A condition that cannot be evaluated stops execution.
+    // Line breakpoints are only hit if execution has just arrived at the line from `previous`
+    fn hit_breakpoint(&mut self, previous: &Option<(String, usize)>, current: &Option<(String, usize)>) -> Result<bool, Error> {
+        // a line is only hit when execution arrives at it, not at every one of its instructions
+        let arrived = current.is_some() && current != previous;
+        let location = Location {
+            line: if arrived { current.clone() } else { None },
+            pc: self.emul.offset().ok(),
+            opcode: self.emul.opcode().ok(),
+            writes: self.writes.borrow().clone(),
         };
+        let dbg: &Self = self;
+        let conditions = dbg.breakpoints.iter()
+            .filter(|b| b.is_at(&location))
+            .map(|b| {
+                let holds = match &b.condition {
+                    Some(c) => Expr::parse(c).and_then(|expr| expr.holds(dbg)).unwrap_or_else(|err| {
+                        warn!("Could not evaluate condition of breakpoint {}: {}", b.id, err);
+                        true
+                    }),
+                    None => true
+                };
+                (b.id, holds)
+            })
+            .collect::<Vec<_>>();
+        let mut stopped = Vec::new();
+        for bp in self.breakpoints.iter_mut() {
+            if conditions.contains(&(bp.id, true)) && bp.hit() {
+                stopped.push(bp.id);
+            }
+        }
+        // the instruction that wrote was on the line execution was at before it
+        let (breakpoints, ids) = (&self.breakpoints, &stopped);
+        let hits = location.writes
+            .iter()
+            .flat_map(|write| {
+                breakpoints.iter()
+                    .filter(move |b| ids.contains(&b.id) && b.watches(write))
+                    .map(move |b| WatchHit { id: b.id, write: write.clone(), line: previous.clone() })
+            })
+            .collect();
+        self.watch_hits = hits;
+        Ok(!stopped.is_empty())
     }
 
-    /// Steps to the next line of execution
-    pub fn step_forward(&mut self) -> Result<(), Error> {
-        // let contract = self.file.find_contract(self.curr_name.as_str())?;
-        debug!("Finding Line from position {}, and contract {}", self.emul.instruction()?, self.curr_name.as_str());
-        let current_line = self.file.lineno_from_opcode_pos(self.emul.instruction()?, self.curr_name.as_str())?;
+    /// Steps to the next line of execution, entering any function that is called.
+    /// Steps a single instruction if there is no source for the code that is executing
+    pub fn step_into(&mut self) -> Result<(), Error> {
+        if self.current_contract().is_none() {
+            self.step_instruction()?;
+            return Ok(());
+        }
+        debug!("Finding Line from position {}, and contract {:?}", self.emul.instruction()?, self.current_contract_name());
+        let current_line = self.source_map()?.lineno_from_opcode_pos(self.emul.instruction()?)?;
         info!("Current Real line: {}", current_line);
         // let offset = self.file.char_pos_from_lineno(current_line, self.curr_name.as_str())?;
 
@@ -103,63 +561,490 @@ impl<T> Debugger<T> where T: web3::Transport {
         let (func_start, func_end) = function.location;
         */
 
-        self.step_loop(|line| *line != current_line)?;
+        self.step_loop(|line, _| line != current_line)?;
+        Ok(())
+    }
+
+    /// Steps to the next line of the current function, executing any function it calls
+    /// without stopping. Stops in the caller if the current function returns
+    pub fn step_over(&mut self) -> Result<(), Error> {
+        if self.current_contract().is_none() {
+            return self.step_into();
+        }
+        let current_line = self.source_map()?.lineno_from_opcode_pos(self.emul.instruction()?)?;
+        let level = self.call_level();
+        self.step_loop(|line, lvl| lvl < level || (lvl == level && line != current_line))?;
         Ok(())
     }
 
+    /// Steps back to the line executed before the current one.
+    /// Instructions without source are stepped back over
+    pub fn step_backward(&mut self) -> Result<(), Error> {
+        let current = self.line();
+        while !self.stepped.is_empty() {
+            self.step_back()?;
+            let line = self.line();
+            if line.is_some() && line != current {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // line of the next instruction to be executed, if its source is known
+    fn line(&self) -> Option<usize> {
+        self.current_contract()?;
+        self.source_map().ok()?.lineno_from_opcode_pos(self.emul.instruction().ok()?).ok()
+    }
+
+    /// Runs until the current function returns to its caller
+    pub fn step_out(&mut self) -> Result<(), Error> {
+        let level = self.call_level();
+        self.step_loop(|_, lvl| lvl < level)?;
+        Ok(())
+    }
+
+    /// How many functions are on the call stack, counting both external frames and internal
+    /// function calls
+    fn call_level(&self) -> usize {
+        self.calls.iter().map(|c| c.len() + 1).sum()
+    }
+
+    /// steps until `fun` is true for the line and call level of the next instruction to be executed
     fn step_loop<F>(&mut self, fun: F) -> Result<(), Error>
     where
-        F: Fn(&usize) -> bool
+        F: Fn(usize, usize) -> bool
     {
         'step: loop {
-            let line = self.file.lineno_from_opcode_pos(self.emul.instruction()?, self.curr_name.as_str())?;
+            // step through code without source until execution returns to code that has it
+            if self.current_contract().is_none() && !self.emul.finished() {
+                self.step_instruction()?;
+                continue 'step;
+            }
+            let line = self.source_map()?.lineno_from_opcode_pos(self.emul.instruction()?)?;
             // let char_offset = self.file.char_pos_from_lineno(line, self.curr_name.as_str())?;
             info!("Current Deciphered Line from next opcode to be executed: {}", line);
-            if fun(&line) || self.emul.finished() {
+            if fun(line, self.call_level()) || self.emul.finished() {
                 info!("Finished!");
                 break 'step;
             } else {
-                self.emul.fire(Action::StepForward)?;
+                self.step_instruction()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Steps a single instruction, keeping track of internal function calls
+    fn step_instruction(&mut self) -> Result<(), Error> {
+        self.writes.borrow_mut().clear();
+        let depth = self.emul.depth();
+        let jump = match (self.emul.instruction(), self.source_map()) {
+            (Ok(pos), Ok(map)) => map.jump(pos).ok().map(|kind| (pos, kind)),
+            _ => None
+        };
+        let mut covered = None;
+        if self.coverage.is_some() && self.emul.executing() {
+            covered = match (self.current_contract(), self.emul.instruction()) {
+                (Some((contract, kind)), Ok(pos)) => Some((contract.name().to_string(), kind, pos)),
+                _ => None
+            };
+        }
+        self.emul.fire(Action::StepForward)?;
+        self.update_contracts();
+        if let (Some(coverage), Some((name, kind, pos))) = (self.coverage.as_mut(), covered.as_ref()) {
+            coverage.record(name, *kind, *pos);
+        }
+
+        // one stack of internal calls for every frame
+        let before = self.calls.len();
+        let frames = self.emul.depth().map(|d| d + 1).unwrap_or(0);
+        let left = if before > frames { self.calls.split_off(frames) } else { Vec::new() };
+        while self.calls.len() < frames {
+            self.calls.push(Vec::new());
+        }
+        let mut change = if before != frames { Some(CallChange::Frames(before, left)) } else { None };
+        if depth == self.emul.depth() {
+            let stack_height = self.stack_height();
+            if let Some(calls) = self.calls.last_mut() {
+                change = match jump {
+                    Some((call_site, JumpKind::IntoFunction)) => {
+                        calls.push(InternalCall { call_site, stack_height });
+                        Some(CallChange::Entered)
+                    },
+                    Some((_, JumpKind::ReturnFromFunction)) => calls.pop().map(CallChange::Returned),
+                    _ => change
+                };
             }
         }
+        self.stepped.push(Stepped { calls: change, covered });
         Ok(())
     }
 
-    /// Jumps to the next breakpoint in execution
+    /// The call stack, innermost function first.
+    /// Every external frame contributes the internal functions it has called
+    pub fn backtrace(&self) -> Result<Vec<StackFrame>, Error> {
+        let frames = self.emul.frames();
+        let mut trace = Vec::new();
+        for (depth, frame) in frames.iter().enumerate() {
+            // an outer frame is paused at the instruction that called into the next one
+            let position = match frames.get(depth + 1) {
+                Some(child) => {
+                    let call_site = child.call_site.expect("Only the outermost frame has no call site; qed");
+                    Emulator::<T, P>::into_instruction(call_site, frame.code.as_slice())
+                },
+                None => self.emul.instruction()?
+            };
+            let contract = self.frame_contract(depth);
+            let calls = self.calls.get(depth).map(|c| c.as_slice()).unwrap_or(&[]);
+            for pos in calls.iter().map(|c| c.call_site).chain(std::iter::once(position)) {
+                trace.push(self.stack_frame(contract, frame, pos));
+            }
+        }
+        trace.reverse();
+        Ok(trace)
+    }
+
+    fn stack_frame(&self, contract: Option<(&Contract, CodeKind)>, frame: &Frame, position: usize) -> StackFrame {
+        let address = ethereum_types::H160(frame.code_address.0);
+        let (contract, kind) = match contract {
+            Some(c) => c,
+            None => return StackFrame { address, contract: None, function: None, file: None, line: None }
+        };
+        let map = contract.source_map_for(kind);
+        let source = self.source_file(contract, &*map, position);
+        StackFrame {
+            address,
+            contract: Some(contract.name().to_string()),
+            function: self.function_at(&source, &*map, position),
+            file: Some(source.file_name().to_string()),
+            line: map.lineno_from_opcode_pos(position).ok(),
+        }
+    }
+
+    // name of the function of `source` that the instruction at `position` was compiled from
+    fn function_at(&self, source: &ContractFile, map: &dyn SourceMap, position: usize) -> Option<String> {
+        map.source_range(position).ok().and_then(|(start, _)| {
+            source.find_function(&mut |func| {
+                let (func_start, func_end) = func.location();
+                func_start <= start && start <= func_end
+            })
+        }).map(|f| f.name)
+    }
+
+    /// Runs until execution arrives at a line with a breakpoint that stops it, or until a frame
+    /// fails. Runs to the end of the transaction if neither happens.
+    /// With no breakpoint enabled the VM runs without stepping, so only a failure of the
+    /// transaction's own frame stops it
     pub fn next(&mut self) -> Result<(), Error> {
         debug!("Breakpoints: {:?}", self.breakpoints);
-        if let Some(b) = self.breakpoints.pop() {
-            self.step_loop(|line| *line == b)?;
-        } else {
-            self.emul.fire(Action::Exec)?;
+        self.failure = None;
+        self.watch_hits.clear();
+        let failures = self.emul.failures().len();
+        if !self.breakpoints.any_enabled() && self.coverage.is_none() {
+            self.run_to_end()?;
+            if self.emul.failures().len() > failures {
+                self.failure = self.emul.failures().last().map(|f| self.report(f));
+            }
+            return Ok(());
+        }
+        let mut previous = self.location();
+        while !self.emul.finished() {
+            self.step_instruction()?;
+            if self.emul.failures().len() > failures {
+                self.failure = self.emul.failures().last().map(|f| self.report(f));
+                break;
+            }
+            let location = self.location();
+            if self.hit_breakpoint(&previous, &location)? {
+                break;
+            }
+            previous = location;
+        }
+        Ok(())
+    }
+
+    /// The failure that stopped execution. `None` if execution did not stop because a frame failed
+    pub fn failure(&self) -> Option<&FailureReport> {
+        self.failure.as_ref()
+    }
+
+    /// Every frame that has failed so far, in the order they failed
+    pub fn failures(&self) -> Vec<FailureReport> {
+        self.emul.failures().into_iter().map(|f| self.report(f)).collect()
+    }
+
+    /// Event logs the transaction has emitted so far, decoded with the ABI of the contract that
+    /// emitted them or, failing that, of any contract that declares the event
+    pub fn logs(&self) -> Vec<DecodedLog> {
+        self.emul.logs().into_iter().map(|log| self.decode_log(log)).collect()
+    }
+
+    fn decode_log(&self, log: &EventLog) -> DecodedLog {
+        let topics = log.topics.iter().map(|t| H256(t.0)).collect::<Vec<_>>();
+        let contract = self.contract_of(log.code.as_slice(), log.depth);
+        let emitter = contract.map(|(c, _)| c);
+        let decoded = events::decode(emitter.into_iter().chain(self.file.contracts().iter()).flat_map(|c| c.events()), &topics, &log.data);
+        let (file, line) = match contract {
+            Some((c, kind)) => self.source_line(c, kind, log.code.as_slice(), log.pc),
+            None => (None, None)
+        };
+        DecodedLog {
+            address: ethereum_types::H160(log.address.0),
+            event: decoded.as_ref().map(|(name, _)| name.clone()),
+            params: decoded.map(|(_, params)| params).unwrap_or_default(),
+            topics,
+            data: log.data.clone(),
+            contract: emitter.map(|c| c.name().to_string()),
+            file,
+            line,
+        }
+    }
+
+    // the contract that `code` is the bytecode of. Code of the outermost frame that matches no
+    // contract is assumed to be the contract the transaction was sent to
+    fn contract_of(&self, code: &[u8], depth: usize) -> Option<(&Contract, CodeKind)> {
+        self.file.contracts()
+            .iter()
+            .find_map(|c| c.code_kind(code).map(|kind| (c, kind)))
+            .or_else(|| if depth == 0 { self.root_contract() } else { None })
+    }
+
+    // file and line of the instruction at byte offset `pc` of the code of `contract`
+    fn source_line(&self, contract: &Contract, kind: CodeKind, code: &[u8], pc: usize) -> (Option<String>, Option<usize>) {
+        let map = contract.source_map_for(kind);
+        let position = Emulator::<T, P>::into_instruction(pc, code);
+        (Some(self.source_file(contract, &*map, position).file_name().to_string()), map.lineno_from_opcode_pos(position).ok())
+    }
+
+    // explain a failure, and find the line of source it happened at
+    fn report(&self, failure: &Failure) -> FailureReport {
+        let contract = self.contract_of(failure.code.as_slice(), failure.depth);
+        let mut report = FailureReport {
+            reason: diagnosis::reason(&failure.kind),
+            address: ethereum_types::H160(failure.code_address.0),
+            contract: None,
+            file: None,
+            line: None,
+            pc: failure.pc,
+        };
+        if let Some((contract, kind)) = contract {
+            let (file, line) = self.source_line(contract, kind, failure.code.as_slice(), failure.pc);
+            report.contract = Some(contract.name().to_string());
+            report.file = file;
+            report.line = line;
+        }
+        report
+    }
+
+    /// Runs the rest of the transaction without stopping, attributing the gas used by every
+    /// instruction to the line of source and the function it was compiled from.
+    /// Gas that a call forwards to another contract is attributed to the code of that contract
+    pub fn profile(&mut self) -> Result<Profile, Error> {
+        let mut meter = Meter::new();
+        // the code of every frame executed, the address it was loaded from, and whether it was the
+        // outermost frame. Instructions are measured by their byte offset in one of these
+        let mut codes: Vec<(Rc<Vec<u8>>, bigint::H160, bool)> = Vec::new();
+        while !self.emul.finished() {
+            let depth = self.emul.depth().unwrap_or(0);
+            let executing = self.emul.executing();
+            if executing {
+                let frame = self.emul.frame().ok_or(EvmError::NotInitialized)?;
+                let code = (frame.code.clone(), frame.code_address, depth == 0);
+                let id = match codes.iter().position(|c| Rc::ptr_eq(&c.0, &code.0) && c.1 == code.1 && c.2 == code.2) {
+                    Some(id) => id,
+                    None => {
+                        codes.push(code);
+                        codes.len() - 1
+                    }
+                };
+                meter.execute(depth, (id, self.emul.offset()?), self.emul.gas().unwrap_or(0));
+            }
+            self.step_instruction()?;
+            if executing && self.emul.returning() {
+                meter.exit(depth, self.emul.gas().unwrap_or(0));
+            }
+        }
+
+        let mut lines = HashMap::new();
+        let positions = codes.iter()
+            .map(|(code, _, _)| disasm::instructions(code.as_slice()).map(|i| (i.offset, i.position)).collect::<HashMap<_, _>>())
+            .collect::<Vec<_>>();
+        for ((id, pc), (gas, instructions)) in meter.into_costs() {
+            let (code, address, root) = &codes[id];
+            let line = match self.contract_of(code.as_slice(), if *root { 0 } else { 1 }) {
+                Some((contract, kind)) => {
+                    let map = contract.source_map_for(kind);
+                    let position = positions[id].get(&pc).cloned().unwrap_or(0);
+                    let source = self.source_file(contract, &*map, position);
+                    LineGas {
+                        contract: contract.name().to_string(),
+                        file: Some(source.file_name().to_string()),
+                        line: map.lineno_from_opcode_pos(position).ok(),
+                        function: self.function_at(&source, &*map, position),
+                        source: map.current_line(position).ok().map(|(_, text)| text.trim().to_string()),
+                        gas: 0,
+                        instructions: 0,
+                    }
+                },
+                None => LineGas {
+                    contract: format!("{:#x}", address),
+                    file: None,
+                    line: None,
+                    function: None,
+                    source: None,
+                    gas: 0,
+                    instructions: 0,
+                }
+            };
+            let total = lines.entry((line.contract.clone(), line.file.clone(), line.line)).or_insert(line);
+            total.gas += gas;
+            total.instructions += instructions;
+        }
+
+        let mut functions = HashMap::new();
+        for line in lines.values() {
+            if let Some(function) = &line.function {
+                let total = functions.entry((line.contract.clone(), function.clone())).or_insert(FunctionGas {
+                    contract: line.contract.clone(),
+                    function: function.clone(),
+                    gas: 0,
+                    instructions: 0,
+                });
+                total.gas += line.gas;
+                total.instructions += line.instructions;
+            }
+        }
+        Ok(Profile::new(lines.into_iter().map(|(_, l)| l).collect(), functions.into_iter().map(|(_, f)| f).collect()))
+    }
+
+    /// Runs the rest of the transaction without stopping, logging the state before every instruction
+    /// like the default tracer of geth's `debug_traceTransaction`
+    pub fn trace(&mut self) -> Result<Trace, Error> {
+        self.emul.start_trace();
+        while !self.emul.finished() {
+            self.step_instruction()?;
+        }
+        Ok(self.emul.trace().ok_or(EvmError::NotInitialized)?)
+    }
+
+    /// Steps a single instruction, entering any contract that is called
+    pub fn step_opcode(&mut self) -> Result<(), Error> {
+        self.step_instruction()
+    }
+
+    /// Steps a single instruction. If it calls or creates a contract, the new frame runs until it
+    /// returns without stopping
+    pub fn next_opcode(&mut self) -> Result<(), Error> {
+        let depth = self.emul.depth();
+        self.step_instruction()?;
+        while depth.is_some() && self.emul.depth() > depth && !self.emul.finished() {
+            self.step_instruction()?;
         }
         Ok(())
     }
 
-    /// Returns the current range of execution
+    /// The instructions of the code that is executing around the next one: up to `before` preceding
+    /// it, and up to `after` following it
+    pub fn disassemble(&self, before: usize, after: usize) -> Result<Vec<Disassembled>, Error> {
+        let offset = self.emul.offset()?;
+        let code = self.emul.frame().map(|f| f.code.clone()).ok_or(EvmError::NotInitialized)?;
+        let current = Emulator::<T, P>::into_instruction(offset, code.as_slice());
+        let contract = self.current_contract().map(|(c, kind)| (c, c.source_map_for(kind)));
+        Ok(disasm::instructions(code.as_slice())
+            .skip(current.saturating_sub(before))
+            .take(before.min(current) + after + 1)
+            .map(|instruction| {
+                let position = instruction.position;
+                let (file, line, source) = match &contract {
+                    Some((c, map)) => (
+                        Some(self.source_file(c, &**map, position).file_name().to_string()),
+                        map.lineno_from_opcode_pos(position).ok(),
+                        map.current_range(position).ok()
+                    ),
+                    None => (None, None, None)
+                };
+                Disassembled { current: position == current, instruction, file, line, source }
+            })
+            .collect())
+    }
+
+    /// Returns the current range of execution.
+    /// Without source, this is the next opcode
     pub fn current_range(&self) ->Result<String, Error> {
-        self.file.current_range(self.emul.instruction()?, self.curr_name.as_str())
+        if self.current_contract().is_none() {
+            return Ok(self.opcode_line()?.1);
+        }
+        self.source_map()?.current_range(self.emul.instruction()?)
     }
 
-    /// returns the current line of execution
+    /// returns the current line of execution.
+    /// Without source, this is the position and name of the next opcode
     pub fn current_line(&self) -> Result<(usize, String), Error> {
-        self.file.current_line(self.emul.instruction()?, self.curr_name.as_str())
+        if self.current_contract().is_none() {
+            return self.opcode_line();
+        }
+        self.source_map()?.current_line(self.emul.instruction()?)
     }
 
     /// Returns the `count` number of last lines relative to current line of execution
     pub fn last_lines(&self, count: usize) -> Result<Vec<(usize, String)>, Error> {
-        self.file.last_lines(self.emul.instruction()?, count, self.curr_name.as_str())
+        if self.current_contract().is_none() {
+            return Ok(vec![self.opcode_line()?]);
+        }
+        self.source_map()?.last_lines(self.emul.instruction()?, count)
     }
 
     /// Returns the `count` number of next lines relative to the current line of execution
     pub fn next_lines(&self, count: usize) -> Result<Vec<(usize, String)>, Error> {
-        self.file.next_lines(self.emul.instruction()?, count, self.curr_name.as_str())
+        if self.current_contract().is_none() {
+            return Ok(vec![self.opcode_line()?]);
+        }
+        self.source_map()?.next_lines(self.emul.instruction()?, count)
     }
 
     /// Chain another transaction on the VM, optionally with a new blockheader
     /// executes with previous state of VM
-    pub fn chain(&mut self, tx: ValidTransaction, block: Option<HeaderParams>) {
-        self.emul.chain(tx, block)
+    pub fn chain(&mut self, tx: ValidTransaction, block: Option<HeaderParams>) -> Result<(), Error> {
+        self.emul.chain(tx, block)?;
+        self.calls.clear();
+        self.update_contracts();
+        self.stepped.clear();
+        self.writes.borrow_mut().clear();
+        self.watch_hits.clear();
+        self.failure = None;
+        Ok(())
+    }
+
+    /// Run the transaction against `state` instead of the state of `address` the node holds.
+    /// Must be called before the transaction starts
+    pub fn override_state(&mut self, address: ethereum_types::Address, state: &StateOverride) -> Result<(), Error> {
+        self.emul.override_state(bigint::H160(address.0), state)?;
+        Ok(())
+    }
+
+    /// Whether the transaction has started executing
+    pub fn started(&self) -> bool {
+        self.emul.started()
+    }
+
+    /// Replace item `index` of the stack, counting from the top, with `value`.
+    /// Execution continues from the changed state
+    pub fn set_stack(&mut self, index: usize, value: ethereum_types::U256) -> Result<(), Error> {
+        self.emul.set_stack(index, bigint::M256(bigint::U256(value.0)))?;
+        Ok(())
+    }
+
+    /// Overwrite memory from `offset` with `bytes`. Execution continues from the changed state
+    pub fn set_memory(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Error> {
+        self.emul.set_memory(offset, bytes)?;
+        Ok(())
+    }
+
+    /// Write `value` to `slot` of the storage of the account the current frame executes against.
+    /// Execution continues from the changed state
+    pub fn set_storage(&mut self, slot: ethereum_types::U256, value: ethereum_types::U256) -> Result<(), Error> {
+        self.emul.set_storage(bigint::U256(slot.0), bigint::M256(bigint::U256(value.0)))?;
+        Ok(())
     }
 
     /// get the return value of the function
@@ -185,6 +1070,69 @@ impl<T> Debugger<T> where T: web3::Transport {
         Ok(stack_vec)
     }
 
+    /// The parameters, return variables and local variables of the function that is executing,
+    /// decoded from the stack.
+    ///
+    /// Assumes the stack layout of Solidity before 0.5, where every local variable is allocated
+    /// when the function is entered: the caller pushes the parameters, then the function pushes
+    /// its return variables and local variables in the order they are declared. A local variable
+    /// is left out until it has been declared, and outside of the block it is declared in
+    pub fn locals(&self) -> Result<Vec<(String, Value)>, Error> {
+        Ok(self.local_declarations()?.into_iter().map(|(decl, value)| (decl.name, value)).collect())
+    }
+
+    fn local_declarations(&self) -> Result<Vec<(Declaration, Value)>, Error> {
+        let (contract, kind) = match self.current_contract() {
+            Some(c) => c,
+            None => return Ok(Vec::new())
+        };
+        // outside of a function (ie, in the dispatcher) there are no locals
+        let call = match self.calls.last().and_then(|c| c.last()) {
+            Some(c) => c,
+            None => return Ok(Vec::new())
+        };
+        let map = contract.source_map_for(kind);
+        let position = self.emul.instruction()?;
+        let (start, _) = map.source_range(position)?;
+        let scope = match self.source_file(contract, &*map, position).function_scope(start) {
+            Some(s) => s,
+            None => return Ok(Vec::new())
+        };
+        let base = match call.stack_height.checked_sub(scope.params.len()) {
+            Some(b) => b,
+            None => return Ok(Vec::new())
+        };
+
+        let stack = self.stack()?;
+        let memory = self.memory_bytes()?;
+        Ok(scope.params.iter()
+            .chain(scope.returns.iter())
+            .chain(scope.locals.iter())
+            .enumerate()
+            // stack is ordered from the top
+            .filter_map(|(i, decl)| stack.len().checked_sub(base + i + 1).map(|slot| (decl, stack[slot])))
+            // every local has a slot, but is only shown where it is in scope
+            .filter(|(decl, _)| decl.in_scope(start))
+            .map(|(decl, word)| (decl.clone(), Value::from_stack(&decl.ty, decl.location, word, memory.as_slice())))
+            .collect())
+    }
+
+    // height of the stack of the current frame
+    fn stack_height(&self) -> usize {
+        let mut height = 0;
+        let _ = self.emul.read_raw(|vm| {
+            height = vm.current_state().map(|s| s.stack.len()).unwrap_or(0);
+            Ok(())
+        });
+        height
+    }
+
+    // memory of the current frame, byte by byte
+    fn memory_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mem = self.emul.memory()?;
+        Ok((0..mem.len()).map(|i| mem.read_raw(i.into())).collect())
+    }
+
     /// returns evm memory
     pub fn memory(&self) -> Result<Vec<bigint::M256>, Error> {
         let mut mem_vec = Vec::new();
@@ -198,4 +1146,192 @@ impl<T> Debugger<T> where T: web3::Transport {
     pub fn storage(&self) -> Option<HashMap<bigint::U256, bigint::M256>> {
         self.emul.storage()
     }
+
+    /// A state variable of the contract executing in the current frame, decoded from storage as
+    /// the frame sees it. Slots the transaction has not touched are fetched from the node
+    pub fn state_variable(&self, name: &str) -> Result<Value, Error> {
+        let (ty, position) = self.state(name)?.ok_or_else(|| DebugError::NoVariable(name.to_string()))?;
+        self.layout().decode(&ty, position, &|slot| self.storage_word(slot))
+    }
+
+    /// Whether `name` is a local or state variable in the current frame
+    pub fn in_scope(&self, name: &str) -> bool {
+        self.local(name).ok().and_then(|v| v).is_some() || self.state(name).ok().and_then(|v| v).is_some()
+    }
+
+    /// Evaluate an expression in the current frame, ie `balances[msg.sender] > 0`
+    pub fn eval(&self, expr: &str) -> Result<Value, Error> {
+        Expr::parse(expr)?.eval(self)
+    }
+}
+
+impl<T, P> Scope for Debugger<T, P> where T: web3::Transport, P: Patch {
+    fn local(&self, name: &str) -> Result<Option<(VarType, Value)>, Error> {
+        // later declarations shadow earlier ones
+        Ok(self.local_declarations()?
+            .into_iter()
+            .rev()
+            .find(|(decl, _)| decl.name == name)
+            .map(|(decl, value)| (decl.ty, value)))
+    }
+
+    fn state(&self, name: &str) -> Result<Option<(VarType, Position)>, Error> {
+        let (contract, _) = match self.current_contract() {
+            Some(c) => c,
+            None => return Ok(None)
+        };
+        let vars = self.file.state_variables(contract.name())?;
+        let (positions, _) = self.layout().place(&vars, ethereum_types::U256::zero());
+        Ok(vars.into_iter()
+            .zip(positions.into_iter())
+            .find(|(var, _)| var.name == name)
+            .map(|(var, position)| (var.ty, position)))
+    }
+
+    // storage of the account the current frame executes against
+    fn storage_word(&self, slot: ethereum_types::U256) -> Result<ethereum_types::U256, Error> {
+        let address = self.emul.frame().map(|f| f.address).unwrap_or_else(|| self.emul.resident_address());
+        let word = self.emul.frame_storage_at(address, bigint::U256(slot.0))?;
+        Ok(ethereum_types::U256((word.0).0))
+    }
+
+    fn builtin(&self, object: &str, member: &str) -> Option<Value> {
+        let (tx, header) = (self.emul.transaction(), self.emul.header());
+        let uint = |v: bigint::U256| Value::Uint(ethereum_types::U256(v.0));
+        let address = |a: bigint::H160| Value::Address(ethereum_types::H160(a.0));
+        match (object, member) {
+            ("msg", "sender") => Some(address(self.emul.caller())),
+            ("msg", "value") => Some(uint(self.emul.call_value())),
+            ("msg", "data") => Some(Value::Bytes(self.emul.call_data().to_vec())),
+            ("msg", "sig") => Some(Value::FixedBytes(self.emul.call_data().iter().take(4).cloned().collect())),
+            ("tx", "origin") => tx.caller.map(address),
+            ("tx", "gasprice") => Some(uint(tx.gas_price.into())),
+            ("block", "number") => Some(uint(header.number)),
+            ("block", "timestamp") => Some(Value::Uint(header.timestamp.into())),
+            ("block", "coinbase") => Some(address(header.beneficiary)),
+            ("block", "difficulty") => Some(uint(header.difficulty)),
+            ("block", "gaslimit") => Some(uint(header.gas_limit.into())),
+            _ => None
+        }
+    }
+
+    fn layout(&self) -> Layout<'_> {
+        Layout::new(&self.file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculate::speculate;
+    use edb_test_helpers as edbtest;
+    use edb_compiler::{Language, solidity::Solidity};
+    use edb_emul::ByzantiumPatch;
+    use sputnikvm::TransactionAction;
+    use web3::types::BlockNumber;
+
+    // a call of SimpleStorage
+    fn transaction(contract: &Contract, function: &str, args: &[ethabi::Token]) -> ValidTransaction {
+        ValidTransaction {
+            caller: Some(edbtest::bigint_addr(edbtest::ADDR_CALLER)),
+            gas_price: bigint::Gas::one(),
+            gas_limit: bigint::Gas::from(10000000u64),
+            action: TransactionAction::Call(edbtest::bigint_addr(edbtest::SIMPLE_STORAGE_ADDR)),
+            value: bigint::U256::zero(),
+            input: Rc::new(contract.function(function).unwrap().encode_input(args).unwrap()),
+            nonce: bigint::U256::zero(),
+        }
+    }
+
+    // a debugger for a call of SimpleStorage, running the code compiled from its source
+    fn debugger(function: &str, args: &[ethabi::Token]) -> Debugger<edbtest::MockWeb3Transport, ByzantiumPatch> {
+        let path = edbtest::contract_path(edbtest::Contract::SimpleStorage);
+        let address = edbtest::ethtype_addr(edbtest::SIMPLE_STORAGE_ADDR);
+        let files = Solidity::default().compile(path.clone(), &address).unwrap();
+        let contract = files.contracts().iter().find(|c| c.name() == "SimpleStorage").unwrap().clone();
+        let tx = transaction(&contract, function, args);
+        let client = web3::Web3::new(edbtest::MockWeb3Transport::default());
+        let mut dbg = Debugger::new(path, files, client, tx, BlockId::Number(BlockNumber::Latest), "SimpleStorage").unwrap();
+        // the code the mock serves may have been compiled by another version of solc
+        let code = StateOverride { code: Some(contract.runtime_bytecode().to_vec()), ..StateOverride::default() };
+        dbg.override_state(address, &code).unwrap();
+        dbg
+    }
+
+    speculate! {
+        describe "calls" {
+            before {
+                let mut dbg = debugger("set", &[ethabi::Token::Uint(ethereum_types::U256::from(1337u64))]);
+                while dbg.call_level() < 2 && !dbg.emul.finished() {
+                    dbg.step_instruction().unwrap();
+                }
+            }
+
+            it "keeps internal calls in sync when stepping back" {
+                assert_eq!(dbg.call_level(), 2);
+                dbg.step_back().unwrap();
+                assert_eq!(dbg.call_level(), 1);
+                dbg.step_instruction().unwrap();
+                assert_eq!(dbg.call_level(), 2);
+            }
+
+            it "keeps internal calls in sync when running to the end" {
+                dbg.run_to_end().unwrap();
+                assert_eq!(dbg.call_level(), 1);
+            }
+
+            it "does not count instructions stepped back over twice" {
+                dbg.record_coverage(Coverage::default());
+                let (name, kind) = dbg.current_contract().map(|(c, kind)| (c.name().to_string(), kind)).unwrap();
+                let position = dbg.emul.instruction().unwrap();
+                dbg.step_instruction().unwrap();
+                dbg.step_back().unwrap();
+                dbg.step_instruction().unwrap();
+                assert_eq!(dbg.coverage().unwrap().hits(&name, kind, position), 1);
+            }
+
+            it "knows the variables in scope" {
+                assert!(dbg.in_scope("storedData"));
+                assert!(!dbg.in_scope("s"));
+            }
+
+            it "steps back to the line executed before" {
+                let line = dbg.line();
+                assert!(line.is_some());
+                dbg.step_into().unwrap();
+                assert_ne!(dbg.line(), line);
+                dbg.step_backward().unwrap();
+                assert_eq!(dbg.line(), line);
+            }
+        }
+
+        describe "breakpoints" {
+            before {
+                let mut dbg = debugger("set", &[ethabi::Token::Uint(ethereum_types::U256::from(1337u64))]);
+                let contract = dbg.root_contract().unwrap().0.clone();
+                let tx = transaction(&contract, "set", &[ethabi::Token::Uint(ethereum_types::U256::from(7u64))]);
+                while !dbg.current_line().map(|(_, src)| src.contains("storedData = x")).unwrap_or(false) {
+                    dbg.step_into().unwrap();
+                }
+                let line = dbg.line().unwrap();
+                dbg.set_breakpoint(None, line, None).unwrap();
+            }
+
+            it "stops at a breakpoint in a chained transaction" {
+                dbg.run_to_end().unwrap();
+                dbg.chain(tx, None).unwrap();
+                dbg.next().unwrap();
+                assert_eq!(dbg.line(), Some(line));
+            }
+
+            it "stops at a breakpoint every time the transaction is run again" {
+                for _ in 0..2 {
+                    dbg.chain(tx.clone(), None).unwrap();
+                    dbg.next().unwrap();
+                    assert_eq!(dbg.line(), Some(line));
+                    assert!(!dbg.emul.finished());
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,71 @@
+//! Explaining why a frame of the transaction failed
+use std::fmt;
+use ethabi::{ParamType, Token};
+use edb_emul::FailureKind;
+
+/// A frame that failed, and where in the source it failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailureReport {
+    /// why the frame failed. Reasons Solidity reverts with are decoded
+    pub reason: String,
+    /// address the failing code was loaded from
+    pub address: ethereum_types::Address,
+    pub contract: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    /// byte offset of the failing instruction
+    pub pc: usize,
+}
+
+impl fmt::Display for FailureReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Execution failed: {}", self.reason)?;
+        match (&self.contract, &self.file, self.line) {
+            (Some(contract), Some(file), Some(line)) => write!(f, " in {} at {}:{}", contract, file, line),
+            _ => write!(f, " at {:#x} of {:#x}", self.pc, self.address),
+        }
+    }
+}
+
+/// Describe a failure, decoding the reason a `REVERT` gives if it is one Solidity encodes
+pub fn reason(kind: &FailureKind) -> String {
+    match kind {
+        FailureKind::Revert(data) => revert_reason(data).unwrap_or_else(|| kind.to_string()),
+        other => other.to_string()
+    }
+}
+
+// `require` and `revert` encode their message as `Error(string)`, failed assertions and
+// arithmetic errors as `Panic(uint256)`
+fn revert_reason(data: &[u8]) -> Option<String> {
+    if data.len() < 4 {
+        return None;
+    }
+    let (selector, args) = data.split_at(4);
+    match selector {
+        [0x08, 0xc3, 0x79, 0xa0] => match ethabi::decode(&[ParamType::String], args).ok()?.pop()? {
+            Token::String(message) => Some(format!("reverted: \"{}\"", message)),
+            _ => None
+        },
+        [0x4e, 0x48, 0x7b, 0x71] => match ethabi::decode(&[ParamType::Uint(256)], args).ok()?.pop()? {
+            Token::Uint(code) => Some(format!("panicked with code {:#x}", code)),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculate::speculate;
+
+    speculate! {
+        it "decodes the message of a revert" {
+            let mut data = vec![0x08, 0xc3, 0x79, 0xa0];
+            data.extend(ethabi::encode(&[Token::String("insufficient balance".to_string())]));
+            assert_eq!(reason(&FailureKind::Revert(data)), "reverted: \"insufficient balance\"");
+            assert_eq!(reason(&FailureKind::Revert(vec![0xde, 0xad])), "reverted with 0xdead");
+        }
+    }
+}
@@ -12,6 +12,32 @@ impl From<web3::error::Error> for CacheError {
     }
 }
 
+#[derive(Fail, Debug)]
+pub enum DebugError {
+    #[fail(display = "No source is known for the code executing at {:#x}", _0)]
+    NoSource(bigint::H160),
+    #[fail(display = "No variable named `{}` is in scope", _0)]
+    NoVariable(String),
+    #[fail(display = "No breakpoint number {}", _0)]
+    NoBreakpoint(usize),
+    #[fail(display = "Cannot set a breakpoint at {}", _0)]
+    InvalidBreakpoint(String),
+}
+
+#[derive(Fail, Debug)]
+pub enum EvalError {
+    #[fail(display = "Could not parse expression: {}", _0)]
+    Parse(String),
+    #[fail(display = "`{}` is not defined", _0)]
+    NotFound(String),
+    #[fail(display = "{}", _0)]
+    Type(String),
+    #[fail(display = "Index {} is out of bounds for length {}", _0, _1)]
+    OutOfBounds(ethereum_types::U256, ethereum_types::U256),
+    #[fail(display = "Division by zero")]
+    DivisionByZero,
+}
+
 #[derive(Fail, Debug)]
 pub enum EvmError {
     #[fail(display = "VM not initialized. Run first before doing anything")]
@@ -0,0 +1,597 @@
+//! Evaluation of small Solidity-like expressions against the state of the VM,
+//! ie `balances[msg.sender] + 1 > total`
+use std::fmt;
+use ethereum_types::U256;
+use failure::Error;
+use edb_compiler::{VarType, Location};
+use super::err::EvalError;
+use super::value::{Value, mask, be_bytes};
+use super::storage::{Layout, Position, keccak, mapping_slot};
+
+/// Where the names in an expression are looked up
+pub trait Scope {
+    /// A local variable of the current function, and its type
+    fn local(&self, name: &str) -> Result<Option<(VarType, Value)>, Error>;
+    /// A state variable of the executing contract: its type, and where it starts in storage
+    fn state(&self, name: &str) -> Result<Option<(VarType, Position)>, Error>;
+    /// A word of storage of the executing contract
+    fn storage_word(&self, slot: U256) -> Result<U256, Error>;
+    /// A member of one of the `msg`, `block` or `tx` builtins
+    fn builtin(&self, object: &str, member: &str) -> Option<Value>;
+    /// How the executing contract lays out its storage
+    fn layout(&self) -> Layout<'_>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+// symbol and precedence of every binary operator. Operators that bind tighter come later
+const BINARY_OPS: &[(&str, BinaryOp, u8)] = &[
+    ("||", BinaryOp::Or, 1),
+    ("&&", BinaryOp::And, 2),
+    ("==", BinaryOp::Eq, 3),
+    ("!=", BinaryOp::Ne, 3),
+    ("<", BinaryOp::Lt, 4),
+    ("<=", BinaryOp::Le, 4),
+    (">", BinaryOp::Gt, 4),
+    (">=", BinaryOp::Ge, 4),
+    ("+", BinaryOp::Add, 5),
+    ("-", BinaryOp::Sub, 5),
+    ("*", BinaryOp::Mul, 6),
+    ("/", BinaryOp::Div, 6),
+    ("%", BinaryOp::Mod, 6),
+];
+
+// longer symbols first, so that `<=` is not read as `<`
+const SYMBOLS: &[&str] = &[
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "%", "!", ".", "[", "]", "(", ")"
+];
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = BINARY_OPS.iter().find(|(_, op, _)| op == self).map(|(s, _, _)| *s).unwrap_or("?");
+        write!(f, "{}", symbol)
+    }
+}
+
+/// A parsed expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Name(String),
+    Member(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(input: &str) -> Result<Expr, Error> {
+        let mut parser = Parser { tokens: tokenize(input)?, pos: 0 };
+        let expr = parser.expr(0)?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(t) => Err(EvalError::Parse(format!("unexpected `{}`", t)).into())
+        }
+    }
+
+    /// Evaluate the expression, looking up names in `scope`
+    pub fn eval<S: Scope>(&self, scope: &S) -> Result<Value, Error> {
+        self.operand(scope)?.load(scope)
+    }
+
+    /// Whether the expression evaluates to `true`, or to a non-zero number
+    pub fn holds<S: Scope>(&self, scope: &S) -> Result<bool, Error> {
+        truthy(&self.eval(scope)?)
+    }
+
+    /// The type and storage position of the value the expression refers to.
+    /// An error if the value is not kept in storage
+    pub fn storage<S: Scope>(&self, scope: &S) -> Result<(VarType, Position), Error> {
+        match self.operand(scope)? {
+            Operand::Storage(ty, position) => Ok((ty, position)),
+            _ => Err(EvalError::Type("the value is not kept in storage".to_string()).into())
+        }
+    }
+
+    fn operand<S: Scope>(&self, scope: &S) -> Result<Operand, Error> {
+        match self {
+            Expr::Literal(v) => Ok(Operand::Value(v.clone())),
+            Expr::Name(name) => lookup(name, scope),
+            Expr::Member(object, member) => object.operand(scope)?.member(member, scope),
+            Expr::Index(object, index) => {
+                let index = index.eval(scope)?;
+                object.operand(scope)?.index(&index, scope)
+            },
+            Expr::Unary(op, e) => Ok(Operand::Value(unary(*op, e.eval(scope)?)?)),
+            // short-circuits
+            Expr::Binary(BinaryOp::And, l, r) => {
+                Ok(Operand::Value(Value::Bool(truthy(&l.eval(scope)?)? && truthy(&r.eval(scope)?)?)))
+            },
+            Expr::Binary(BinaryOp::Or, l, r) => {
+                Ok(Operand::Value(Value::Bool(truthy(&l.eval(scope)?)? || truthy(&r.eval(scope)?)?)))
+            },
+            Expr::Binary(op, l, r) => Ok(Operand::Value(binary(*op, l.eval(scope)?, r.eval(scope)?)?)),
+        }
+    }
+}
+
+// an intermediate result. Storage is only read once the value that is needed is known,
+// so that indexing a mapping or array does not decode all of it
+enum Operand {
+    Value(Value),
+    Storage(VarType, Position),
+    /// `msg`, `block` or `tx`
+    Builtin(String),
+}
+
+impl Operand {
+    fn load<S: Scope>(self, scope: &S) -> Result<Value, Error> {
+        match self {
+            Operand::Value(v) => Ok(v),
+            Operand::Storage(ty, position) => scope.layout().decode(&ty, position, &|slot| scope.storage_word(slot)),
+            Operand::Builtin(name) => Err(EvalError::Type(format!("`{}` is not a value", name)).into())
+        }
+    }
+
+    fn member<S: Scope>(self, member: &str, scope: &S) -> Result<Operand, Error> {
+        match self {
+            Operand::Builtin(object) => {
+                scope.builtin(&object, member)
+                    .map(Operand::Value)
+                    .ok_or_else(|| EvalError::NotFound(format!("{}.{}", object, member)).into())
+            },
+            Operand::Storage(VarType::Struct(name), position) => {
+                let (ty, position) = scope.layout()
+                    .member(&name, member, position.slot)
+                    .ok_or_else(|| EvalError::NotFound(format!("{}.{}", name, member)))?;
+                Ok(Operand::Storage(ty, position))
+            },
+            Operand::Storage(VarType::Array(_, len), position) if member == "length" => {
+                let len = match len {
+                    Some(len) => U256::from(len),
+                    None => scope.storage_word(position.slot)?
+                };
+                Ok(Operand::Value(Value::Uint(len)))
+            },
+            other => match (other.load(scope)?, member) {
+                (Value::Struct(members), _) => {
+                    members.into_iter()
+                        .find(|(name, _)| name == member)
+                        .map(|(_, value)| Operand::Value(value))
+                        .ok_or_else(|| EvalError::NotFound(member.to_string()).into())
+                },
+                (Value::Array(elements), "length") => Ok(Operand::Value(Value::Uint(elements.len().into()))),
+                (Value::Bytes(bytes), "length") | (Value::FixedBytes(bytes), "length") => {
+                    Ok(Operand::Value(Value::Uint(bytes.len().into())))
+                },
+                (value, _) => Err(EvalError::Type(format!("`{}` has no member `{}`", value, member)).into())
+            }
+        }
+    }
+
+    fn index<S: Scope>(self, index: &Value, scope: &S) -> Result<Operand, Error> {
+        match self {
+            Operand::Storage(VarType::Mapping(_, value), position) => {
+                let slot = mapping_slot(&key_bytes(index)?, position.slot);
+                Ok(Operand::Storage(*value, Position { slot, offset: 0 }))
+            },
+            Operand::Storage(VarType::Array(elem, len), position) => {
+                let (base, len) = match len {
+                    Some(len) => (position.slot, U256::from(len)),
+                    None => (keccak(&be_bytes(position.slot)), scope.storage_word(position.slot)?)
+                };
+                let i = as_index(index, len)?;
+                let position = scope.layout().element(&elem, base, i);
+                Ok(Operand::Storage(*elem, position))
+            },
+            other => match other.load(scope)? {
+                Value::Array(elements) => {
+                    let i = as_index(index, elements.len().into())?;
+                    Ok(Operand::Value(elements.into_iter().nth(i).expect("index is in bounds; qed")))
+                },
+                Value::Bytes(bytes) | Value::FixedBytes(bytes) => {
+                    let i = as_index(index, bytes.len().into())?;
+                    Ok(Operand::Value(Value::FixedBytes(vec![bytes[i]])))
+                },
+                value => Err(EvalError::Type(format!("`{}` cannot be indexed", value)).into())
+            }
+        }
+    }
+}
+
+fn lookup<S: Scope>(name: &str, scope: &S) -> Result<Operand, Error> {
+    if let Some((ty, value)) = scope.local(name)? {
+        // storage pointers are followed like state variables
+        return Ok(match value {
+            Value::Pointer(Location::Storage, slot) => Operand::Storage(ty, Position { slot, offset: 0 }),
+            value => Operand::Value(value)
+        });
+    }
+    if let Some((ty, position)) = scope.state(name)? {
+        return Ok(Operand::Storage(ty, position));
+    }
+    match name {
+        "msg" | "block" | "tx" => Ok(Operand::Builtin(name.to_string())),
+        "now" => scope.builtin("block", "timestamp").map(Operand::Value).ok_or_else(|| EvalError::NotFound(name.to_string()).into()),
+        _ => Err(EvalError::NotFound(name.to_string()).into())
+    }
+}
+
+fn unary(op: UnaryOp, value: Value) -> Result<Value, Error> {
+    match op {
+        UnaryOp::Not => Ok(Value::Bool(!truthy(&value)?)),
+        UnaryOp::Neg => {
+            word(&value)
+                .map(|w| Value::Int(negate(w), 256))
+                .ok_or_else(|| EvalError::Type(format!("cannot negate `{}`", value)).into())
+        }
+    }
+}
+
+fn binary(op: BinaryOp, left: Value, right: Value) -> Result<Value, Error> {
+    let is_dynamic = |v: &Value| match v {
+        Value::String(_) | Value::Bytes(_) => true,
+        _ => false
+    };
+    // strings and bytes can only be compared for equality
+    if is_dynamic(&left) || is_dynamic(&right) {
+        return match op {
+            BinaryOp::Eq => Ok(Value::Bool(left == right)),
+            BinaryOp::Ne => Ok(Value::Bool(left != right)),
+            _ => Err(EvalError::Type(format!("cannot apply `{}` to `{}` and `{}`", op, left, right)).into())
+        };
+    }
+    let (l, r) = match (word(&left), word(&right)) {
+        (Some(l), Some(r)) => (l, r),
+        _ => return Err(EvalError::Type(format!("cannot apply `{}` to `{}` and `{}`", op, left, right)).into())
+    };
+    let signed = is_signed(&left) || is_signed(&right);
+    let number = |v: U256| if signed { Value::Int(v, 256) } else { Value::Uint(v) };
+    Ok(match op {
+        BinaryOp::Or => Value::Bool(!l.is_zero() || !r.is_zero()),
+        BinaryOp::And => Value::Bool(!l.is_zero() && !r.is_zero()),
+        BinaryOp::Eq => Value::Bool(l == r),
+        BinaryOp::Ne => Value::Bool(l != r),
+        BinaryOp::Lt => Value::Bool(less(l, r, signed)),
+        BinaryOp::Le => Value::Bool(!less(r, l, signed)),
+        BinaryOp::Gt => Value::Bool(less(r, l, signed)),
+        BinaryOp::Ge => Value::Bool(!less(l, r, signed)),
+        BinaryOp::Add => number(l.overflowing_add(r).0),
+        BinaryOp::Sub => number(l.overflowing_sub(r).0),
+        BinaryOp::Mul => number(l.overflowing_mul(r).0),
+        BinaryOp::Div | BinaryOp::Mod => {
+            if r.is_zero() {
+                return Err(EvalError::DivisionByZero.into());
+            }
+            if signed {
+                let (quotient, remainder) = (abs(l) / abs(r), abs(l) % abs(r));
+                // the quotient is negative if the signs differ, the remainder takes the sign of the dividend
+                match op {
+                    BinaryOp::Div if l.bit(255) != r.bit(255) => number(negate(quotient)),
+                    BinaryOp::Div => number(quotient),
+                    _ if l.bit(255) => number(negate(remainder)),
+                    _ => number(remainder)
+                }
+            } else if op == BinaryOp::Div {
+                number(l / r)
+            } else {
+                number(l % r)
+            }
+        }
+    })
+}
+
+fn truthy(value: &Value) -> Result<bool, Error> {
+    match value {
+        Value::Bool(b) => Ok(*b),
+        value => word(value).map(|w| !w.is_zero()).ok_or_else(|| EvalError::Type(format!("`{}` is not a boolean", value)).into())
+    }
+}
+
+// the word a value type is held in. Signed integers are sign-extended to 256 bits
+fn word(value: &Value) -> Option<U256> {
+    match value {
+        Value::Uint(v) | Value::Raw(v) => Some(*v),
+        Value::Int(v, bits) => {
+            if *bits > 0 && *bits < 256 && v.bit(*bits - 1) {
+                Some(*v | !mask(*bits))
+            } else {
+                Some(*v)
+            }
+        },
+        Value::Address(a) => Some(U256::from_big_endian(&a.0)),
+        Value::Bool(b) => Some(if *b { U256::one() } else { U256::zero() }),
+        // fixed bytes are left-aligned
+        Value::FixedBytes(bytes) => {
+            let mut buf = [0u8; 32];
+            let len = bytes.len().min(32);
+            buf[..len].copy_from_slice(&bytes[..len]);
+            Some(U256::from_big_endian(&buf))
+        },
+        _ => None
+    }
+}
+
+fn is_signed(value: &Value) -> bool {
+    match value {
+        Value::Int(..) => true,
+        _ => false
+    }
+}
+
+// two's complement
+fn negate(v: U256) -> U256 {
+    (!v).overflowing_add(U256::one()).0
+}
+
+fn abs(v: U256) -> U256 {
+    if v.bit(255) { negate(v) } else { v }
+}
+
+fn less(l: U256, r: U256, signed: bool) -> bool {
+    if signed {
+        // flipping the sign bit orders two's complement numbers as unsigned ones
+        let sign = U256::one() << 255;
+        (l ^ sign) < (r ^ sign)
+    } else {
+        l < r
+    }
+}
+
+// keys of mappings are hashed as a padded word, or unpadded for `bytes` and `string`
+fn key_bytes(key: &Value) -> Result<Vec<u8>, Error> {
+    match key {
+        Value::String(s) => Ok(s.as_bytes().to_vec()),
+        Value::Bytes(b) => Ok(b.clone()),
+        key => word(key).map(|w| be_bytes(w).to_vec()).ok_or_else(|| EvalError::Type(format!("`{}` cannot be a key", key)).into())
+    }
+}
+
+fn as_index(index: &Value, len: U256) -> Result<usize, Error> {
+    let i = word(index).ok_or_else(|| EvalError::Type(format!("`{}` is not an index", index)))?;
+    // an index that does not fit in memory cannot be read either
+    if i >= len || i > U256::from(usize::max_value()) {
+        return Err(EvalError::OutOfBounds(i, len).into());
+    }
+    Ok(i.as_u64() as usize)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(U256),
+    Str(String),
+    Ident(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Str(s) => write!(f, "{:?}", s),
+            Token::Ident(i) => write!(f, "{}", i),
+            Token::Symbol(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, Error> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_alphanumeric() {
+                i += 1;
+            }
+            tokens.push(Token::Number(number(&chars[start..i].iter().collect::<String>())?));
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if c == '"' || c == '\'' {
+            let len = chars[i + 1..]
+                .iter()
+                .position(|q| *q == c)
+                .ok_or_else(|| EvalError::Parse("unterminated string".to_string()))?;
+            tokens.push(Token::Str(chars[i + 1..i + 1 + len].iter().collect()));
+            i += len + 2;
+        } else {
+            let rest: String = chars[i..].iter().take(2).collect();
+            let symbol = SYMBOLS.iter()
+                .find(|s| rest.starts_with(**s))
+                .ok_or_else(|| EvalError::Parse(format!("unexpected `{}`", c)))?;
+            tokens.push(Token::Symbol(symbol));
+            i += symbol.len();
+        }
+    }
+    Ok(tokens)
+}
+
+// decimal, or hexadecimal if prefixed with `0x`
+fn number(literal: &str) -> Result<U256, Error> {
+    let (digits, radix) = if literal.starts_with("0x") {
+        (&literal[2..], 16)
+    } else {
+        (literal, 10)
+    };
+    let invalid = || EvalError::Parse(format!("`{}` is not a number", literal));
+    if digits.is_empty() {
+        return Err(invalid().into());
+    }
+    digits.chars().try_fold(U256::zero(), |acc, c| {
+        let digit = c.to_digit(radix).ok_or_else(invalid)?;
+        let (shifted, overflow) = acc.overflowing_mul(U256::from(radix));
+        let (sum, carry) = shifted.overflowing_add(U256::from(digit));
+        if overflow || carry {
+            Err(invalid().into())
+        } else {
+            Ok(sum)
+        }
+    })
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        match self.peek() {
+            Some(Token::Symbol(s)) if *s == symbol => {
+                self.pos += 1;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), Error> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(EvalError::Parse(format!("expected `{}`", symbol)).into())
+        }
+    }
+
+    // binary operators by precedence climbing. Every operator is left-associative
+    fn expr(&mut self, min_precedence: u8) -> Result<Expr, Error> {
+        let mut left = self.unary()?;
+        loop {
+            let (op, precedence) = match self.peek() {
+                Some(Token::Symbol(s)) => match BINARY_OPS.iter().find(|(sym, _, _)| sym == s) {
+                    Some((_, op, precedence)) if *precedence >= min_precedence => (*op, *precedence),
+                    _ => break
+                },
+                _ => break
+            };
+            self.pos += 1;
+            let right = self.expr(precedence + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        if self.eat("!") {
+            Ok(Expr::Unary(UnaryOp::Not, Box::new(self.unary()?)))
+        } else if self.eat("-") {
+            Ok(Expr::Unary(UnaryOp::Neg, Box::new(self.unary()?)))
+        } else {
+            self.postfix()
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat(".") {
+                match self.next() {
+                    Some(Token::Ident(member)) => expr = Expr::Member(Box::new(expr), member),
+                    _ => return Err(EvalError::Parse("expected a member name after `.`".to_string()).into())
+                }
+            } else if self.eat("[") {
+                let index = self.expr(0)?;
+                self.expect("]")?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expr::Literal(Value::Uint(n))),
+            Some(Token::Str(s)) => Ok(Expr::Literal(Value::String(s))),
+            Some(Token::Ident(ref i)) if i == "true" => Ok(Expr::Literal(Value::Bool(true))),
+            Some(Token::Ident(ref i)) if i == "false" => Ok(Expr::Literal(Value::Bool(false))),
+            Some(Token::Ident(i)) => Ok(Expr::Name(i)),
+            Some(Token::Symbol("(")) => {
+                let expr = self.expr(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            },
+            Some(t) => Err(EvalError::Parse(format!("unexpected `{}`", t)).into()),
+            None => Err(EvalError::Parse("unexpected end of expression".to_string()).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculate::speculate;
+
+    speculate! {
+        it "parses by precedence" {
+            let expr = Expr::parse("a + b * 2 > c && !d").unwrap();
+            let name = |n: &str| Box::new(Expr::Name(n.to_string()));
+            assert_eq!(expr, Expr::Binary(BinaryOp::And,
+                Box::new(Expr::Binary(BinaryOp::Gt,
+                    Box::new(Expr::Binary(BinaryOp::Add, name("a"),
+                        Box::new(Expr::Binary(BinaryOp::Mul, name("b"), Box::new(Expr::Literal(Value::Uint(2.into()))))))),
+                    name("c"))),
+                Box::new(Expr::Unary(UnaryOp::Not, name("d")))));
+        }
+
+        it "parses member access and indexing" {
+            let expr = Expr::parse("balances[msg.sender].amount").unwrap();
+            assert_eq!(expr, Expr::Member(
+                Box::new(Expr::Index(
+                    Box::new(Expr::Name("balances".to_string())),
+                    Box::new(Expr::Member(Box::new(Expr::Name("msg".to_string())), "sender".to_string())))),
+                "amount".to_string()));
+        }
+
+        it "does signed arithmetic" {
+            let minus_seven = Value::Int(negate(7.into()), 256);
+            assert_eq!(binary(BinaryOp::Div, minus_seven.clone(), Value::Uint(2.into())).unwrap(), Value::Int(negate(3.into()), 256));
+            assert_eq!(binary(BinaryOp::Mod, minus_seven.clone(), Value::Uint(2.into())).unwrap(), Value::Int(negate(1.into()), 256));
+            assert_eq!(binary(BinaryOp::Lt, minus_seven, Value::Uint(0.into())).unwrap(), Value::Bool(true));
+        }
+
+        it "rejects indices too large to address" {
+            assert!(as_index(&Value::Uint(U256::max_value() - 1), U256::max_value()).is_err());
+            assert_eq!(as_index(&Value::Uint(2.into()), 3.into()).unwrap(), 2);
+        }
+    }
+}
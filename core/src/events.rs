@@ -0,0 +1,99 @@
+//! Decoding event logs with the ABI of the contracts that declare them
+use std::fmt;
+use ethabi::{Event, RawLog};
+use ethereum_types::{Address, H256};
+
+/// An event log emitted by the transaction, and the line of source that emitted it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedLog {
+    /// account the log is emitted from
+    pub address: Address,
+    /// name of the event. `None` if no contract declares it
+    pub event: Option<String>,
+    /// names and values of the parameters of the event
+    pub params: Vec<(String, String)>,
+    pub topics: Vec<H256>,
+    pub data: Vec<u8>,
+    /// contract that emitted the log
+    pub contract: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+}
+
+impl fmt::Display for DecodedLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.event {
+            Some(event) => {
+                let params = self.params.iter().map(|(name, value)| format!("{}: {}", name, value)).collect::<Vec<_>>();
+                write!(f, "{}({})", event, params.join(", "))?;
+            },
+            None => {
+                let topics = self.topics.iter().map(|t| format!("{:#x}", t)).collect::<Vec<_>>();
+                write!(f, "topics: [{}], data: 0x", topics.join(", "))?;
+                for byte in self.data.iter() {
+                    write!(f, "{:02x}", byte)?;
+                }
+            }
+        }
+        write!(f, " from {:#x}", self.address)?;
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, " at {}:{}", file, line),
+            _ => Ok(())
+        }
+    }
+}
+
+/// Name and parameters of the first of `events` whose signature is the first topic of a log.
+/// Anonymous events are never matched, since they have no signature
+pub(crate) fn decode<'a>(events: impl Iterator<Item = &'a Event>, topics: &[H256], data: &[u8]) -> Option<(String, Vec<(String, String)>)> {
+    let signature = topics.first()?;
+    events
+        .filter(|event| !event.anonymous && event.signature() == *signature)
+        .find_map(|event| {
+            let log = event.parse_log(RawLog { topics: topics.to_vec(), data: data.to_vec() }).ok()?;
+            Some((event.name.clone(), log.params.into_iter().map(|p| (p.name, format!("{}", p.value))).collect()))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculate::speculate;
+    use ethabi::{EventParam, ParamType, Token};
+    use ethereum_types::U256;
+
+    speculate! {
+        before {
+            let param = |name: &str, kind, indexed| EventParam { name: name.to_string(), kind, indexed };
+            let transfer = Event {
+                name: "Transfer".to_string(),
+                inputs: vec![param("from", ParamType::Address, true), param("value", ParamType::Uint(256), false)],
+                anonymous: false,
+            };
+            let from = Address::from([7u8; 20]);
+            let value = U256::from(1337u64);
+            let topics = vec![transfer.signature(), H256::from(from)];
+            let data = ethabi::encode(&[Token::Uint(value)]);
+        }
+
+        it "decodes indexed and non-indexed parameters" {
+            let decoded = decode(std::iter::once(&transfer), &topics, &data);
+            assert_eq!(decoded, Some(("Transfer".to_string(), vec![
+                ("from".to_string(), format!("{}", Token::Address(from))),
+                ("value".to_string(), format!("{}", Token::Uint(value))),
+            ])));
+        }
+
+        it "does not decode logs of other events" {
+            let mut other = transfer;
+            other.name = "Approval".to_string();
+            assert_eq!(decode(std::iter::once(&other), &topics, &data), None);
+        }
+
+        it "does not decode anonymous events" {
+            let mut anonymous = transfer;
+            anonymous.anonymous = true;
+            assert_eq!(decode(std::iter::once(&anonymous), &topics, &data), None);
+        }
+    }
+}
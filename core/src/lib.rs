@@ -1,9 +1,27 @@
+mod breakpoint;
 mod debug;
 mod err;
+mod eval;
+mod events;
+mod coverage;
+mod diagnosis;
+mod profile;
 mod addr_cache;
-pub use self::debug::Debugger;
-pub use edb_compiler::{Language, solidity::Solidity, CompiledFiles, Contract, ContractFile};
+mod storage;
+mod value;
+pub use self::debug::{Debugger, StackFrame, Disassembled};
+pub use self::value::Value;
+pub use self::breakpoint::{Breakpoint, Breakpoints, Target, WatchHit};
+pub use self::diagnosis::FailureReport;
+pub use self::events::DecodedLog;
+pub use self::profile::{Profile, LineGas, FunctionGas};
+pub use self::coverage::Coverage;
+pub use self::eval::{Expr, Scope};
+pub use self::storage::{Layout, Position};
+pub use edb_compiler::{Language, solidity::Solidity, CompiledFiles, Contract, ContractFile, CodeKind, FoundationVersion};
 pub use web3::Transport;
+pub use edb_emul::{disasm::Instruction, Write, Trace, StructLog, Session, Recorder, Replay, StateCache, StateOverride};
+pub use edb_emul::{Patch, FrontierPatch, HomesteadPatch, SpuriousDragonPatch, ByzantiumPatch};
 
 pub mod contract {
     pub use edb_compiler::Find;
@@ -0,0 +1,163 @@
+//! Measuring the gas used by each line of source, and by each function
+use std::{fmt, hash::Hash, collections::HashMap};
+use failure::Error;
+use serde_derive::Serialize;
+
+/// Gas used by the instructions of one line of source
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LineGas {
+    /// contract the line is compiled into, or the address of code with no known source
+    pub contract: String,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    /// function the line is in
+    pub function: Option<String>,
+    /// source code of the line
+    pub source: Option<String>,
+    pub gas: u64,
+    /// instructions of the line that were executed
+    pub instructions: usize,
+}
+
+impl fmt::Display for LineGas {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>10} {:>8}  ", self.gas, self.instructions)?;
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{}", file, line)?,
+            _ => write!(f, "{}", self.contract)?,
+        }
+        if let Some(source) = &self.source {
+            write!(f, "  {}", source)?;
+        }
+        Ok(())
+    }
+}
+
+/// Gas used by the instructions of one function, not counting other contracts it calls
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FunctionGas {
+    pub contract: String,
+    pub function: String,
+    pub gas: u64,
+    pub instructions: usize,
+}
+
+impl fmt::Display for FunctionGas {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>10} {:>8}  {}.{}", self.gas, self.instructions, self.contract, self.function)
+    }
+}
+
+/// Gas used by a transaction, attributed to the lines and functions that used it.
+/// Lines and functions are sorted by the gas they used, most first
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Profile {
+    /// gas used by every instruction executed, not counting the intrinsic gas of the transaction
+    pub gas: u64,
+    pub lines: Vec<LineGas>,
+    pub functions: Vec<FunctionGas>,
+}
+
+impl Profile {
+    /// Sort the lines and functions of a profile, and add up the gas they used
+    pub(crate) fn new(mut lines: Vec<LineGas>, mut functions: Vec<FunctionGas>) -> Self {
+        lines.sort_by(|a, b| b.gas.cmp(&a.gas).then(b.instructions.cmp(&a.instructions)));
+        functions.sort_by(|a, b| b.gas.cmp(&a.gas).then(b.instructions.cmp(&a.instructions)));
+        Profile { gas: lines.iter().map(|l| l.gas).sum(), lines, functions }
+    }
+
+    /// The profile as JSON
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+// an instruction of a frame, waiting for the frame to execute its next one to know what it cost
+#[derive(Debug)]
+struct Metered<K> {
+    pending: Option<(K, u64)>,
+    /// gas used by the frames the pending instruction called
+    callees: u64,
+    /// gas used by the frame and every frame it called
+    spent: u64,
+}
+
+/// Measures the gas of each instruction as the drop in gas left to its frame by the time the frame
+/// executes its next instruction, or finishes. The gas of an instruction that calls another frame
+/// does not include the gas used by that frame
+#[derive(Debug)]
+pub(crate) struct Meter<K: Hash + Eq + Clone> {
+    frames: Vec<Metered<K>>,
+    costs: HashMap<K, (u64, usize)>,
+}
+
+impl<K: Hash + Eq + Clone> Meter<K> {
+    pub fn new() -> Self {
+        Meter { frames: Vec::new(), costs: HashMap::new() }
+    }
+
+    /// The instruction `key` is about to be executed in the frame at call depth `depth`,
+    /// with `gas` left to the frame
+    pub fn execute(&mut self, depth: usize, key: K, gas: u64) {
+        while self.frames.len() <= depth {
+            self.frames.push(Metered { pending: None, callees: 0, spent: 0 });
+        }
+        self.settle(depth, gas);
+        self.costs.entry(key.clone()).or_insert((0, 0)).1 += 1;
+        self.frames[depth].pending = Some((key, gas));
+    }
+
+    /// The frame at call depth `depth` has finished, with `gas` left to it
+    pub fn exit(&mut self, depth: usize, gas: u64) {
+        if depth >= self.frames.len() {
+            return;
+        }
+        self.settle(depth, gas);
+        let spent = self.frames[depth].spent;
+        self.frames.truncate(depth);
+        if let Some(caller) = self.frames.last_mut() {
+            caller.callees += spent;
+        }
+    }
+
+    /// The gas used by each instruction, and how many times it was executed
+    pub fn into_costs(self) -> HashMap<K, (u64, usize)> {
+        self.costs
+    }
+
+    // attribute gas to the instruction the frame at `depth` executed last
+    fn settle(&mut self, depth: usize, gas: u64) {
+        let frame = &mut self.frames[depth];
+        if let Some((key, before)) = frame.pending.take() {
+            let cost = before.saturating_sub(gas);
+            frame.spent += cost;
+            let own = cost.saturating_sub(frame.callees);
+            frame.callees = 0;
+            self.costs.entry(key).or_insert((0, 0)).0 += own;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculate::speculate;
+
+    speculate! {
+        it "leaves the gas of a callee out of the call" {
+            let mut meter = Meter::new();
+            meter.execute(0, "PUSH1", 1000);
+            meter.execute(0, "CALL", 997);
+            // the callee is given 500 gas, uses 30 and returns the rest
+            meter.execute(1, "SSTORE", 500);
+            meter.exit(1, 470);
+            meter.execute(0, "STOP", 997 - 700 - 30);
+            meter.exit(0, 997 - 700 - 30);
+            let costs = meter.into_costs();
+            assert_eq!(costs["PUSH1"], (3, 1));
+            assert_eq!(costs["CALL"], (700, 1));
+            assert_eq!(costs["SSTORE"], (30, 1));
+            assert_eq!(costs["STOP"], (0, 1));
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! Layout of state variables in storage, and decoding them from it
+use ethereum_types::U256;
+use failure::Error;
+use tiny_keccak::keccak256;
+use edb_compiler::{CodeFile, Declaration, VarType, Location};
+use super::value::{Value, MAX_ELEMENTS, mask, be_bytes};
+
+/// Where a value starts in storage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub slot: U256,
+    /// bytes from the low-order end of the slot
+    pub offset: usize,
+}
+
+/// Derives where variables are stored, following the packing rules of the Solidity compiler
+pub struct Layout<'a> {
+    file: &'a CodeFile,
+}
+
+impl<'a> Layout<'a> {
+    pub fn new(file: &'a CodeFile) -> Self {
+        Self { file }
+    }
+
+    /// Positions of variables laid out one after another starting at slot `base`,
+    /// and the number of slots they take up altogether
+    pub fn place(&self, vars: &[Declaration], base: U256) -> (Vec<Position>, usize) {
+        let (mut slot, mut offset) = (0usize, 0usize);
+        let mut positions = Vec::with_capacity(vars.len());
+        for var in vars.iter() {
+            match packed_size(&var.ty) {
+                Some(size) => {
+                    if offset + size > 32 {
+                        slot += 1;
+                        offset = 0;
+                    }
+                    positions.push(Position { slot: base + U256::from(slot), offset });
+                    offset += size;
+                },
+                // reference types always start a new slot, and the variable after them does too
+                None => {
+                    if offset > 0 {
+                        slot += 1;
+                        offset = 0;
+                    }
+                    positions.push(Position { slot: base + U256::from(slot), offset: 0 });
+                    slot += self.slots(&var.ty);
+                }
+            }
+        }
+        (positions, if offset > 0 { slot + 1 } else { slot })
+    }
+
+    /// Number of slots a variable of type `ty` takes up
+    pub fn slots(&self, ty: &VarType) -> usize {
+        match ty {
+            VarType::Struct(name) => {
+                self.file.struct_decl(name).map(|s| self.place(&s.members, U256::zero()).1).unwrap_or(1)
+            },
+            VarType::Array(elem, Some(len)) => match packed_size(elem) {
+                Some(size) => {
+                    let per_slot = 32 / size;
+                    (len + per_slot - 1) / per_slot
+                },
+                None => len * self.slots(elem)
+            },
+            // mappings, dynamic arrays and strings keep their data elsewhere
+            _ => 1
+        }
+    }
+
+    /// Position of element `index` of an array of `elem` whose elements start at slot `base`
+    pub fn element(&self, elem: &VarType, base: U256, index: usize) -> Position {
+        match packed_size(elem) {
+            Some(size) => {
+                let per_slot = 32 / size;
+                Position { slot: base + U256::from(index / per_slot), offset: (index % per_slot) * size }
+            },
+            None => Position { slot: base + U256::from(index * self.slots(elem)), offset: 0 }
+        }
+    }
+
+    /// Type and position of a member of the struct `name` stored from slot `base`
+    pub fn member(&self, name: &str, member: &str, base: U256) -> Option<(VarType, Position)> {
+        let decl = self.file.struct_decl(name)?;
+        let (positions, _) = self.place(&decl.members, base);
+        decl.members
+            .into_iter()
+            .zip(positions.into_iter())
+            .find(|(m, _)| m.name == member)
+            .map(|(m, pos)| (m.ty, pos))
+    }
+
+    /// Decode a value of type `ty` that starts at `position`, reading words of storage with `read`
+    pub fn decode<F>(&self, ty: &VarType, position: Position, read: &F) -> Result<Value, Error>
+    where
+        F: Fn(U256) -> Result<U256, Error>
+    {
+        if let Some(size) = packed_size(ty) {
+            let word = (read(position.slot)? >> (position.offset * 8)) & mask(size * 8);
+            // fixed bytes are decoded left-aligned
+            let word = match ty {
+                VarType::FixedBytes(_) => word << ((32 - size) * 8),
+                _ => word
+            };
+            return Ok(Value::from_word(ty, word));
+        }
+        match ty {
+            VarType::Struct(name) => {
+                let decl = match self.file.struct_decl(name) {
+                    Some(d) => d,
+                    None => return Ok(Value::Pointer(Location::Storage, position.slot))
+                };
+                let (positions, _) = self.place(&decl.members, position.slot);
+                let members = decl.members
+                    .iter()
+                    .zip(positions.into_iter())
+                    .map(|(member, pos)| Ok((member.name.clone(), self.decode(&member.ty, pos, read)?)))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                Ok(Value::Struct(members))
+            },
+            VarType::Array(elem, Some(len)) => self.elements(elem, position.slot, *len, read),
+            // the length is stored in the slot, and the elements from the hash of the slot
+            VarType::Array(elem, None) => {
+                let len = read(position.slot)?.min(U256::from(MAX_ELEMENTS)).as_u64() as usize;
+                self.elements(elem, keccak(&be_bytes(position.slot)), len, read)
+            },
+            VarType::Bytes => Ok(Value::Bytes(bytes(position.slot, read)?)),
+            VarType::String => Ok(Value::String(String::from_utf8_lossy(&bytes(position.slot, read)?).into_owned())),
+            VarType::Unknown(_) => Ok(Value::Raw(read(position.slot)?)),
+            // mappings cannot be listed, since the keys are not stored
+            _ => Ok(Value::Pointer(Location::Storage, position.slot))
+        }
+    }
+
+    fn elements<F>(&self, elem: &VarType, base: U256, len: usize, read: &F) -> Result<Value, Error>
+    where
+        F: Fn(U256) -> Result<U256, Error>
+    {
+        let elements = (0..len.min(MAX_ELEMENTS))
+            .map(|i| self.decode(elem, self.element(elem, base, i), read))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Value::Array(elements))
+    }
+}
+
+/// Keccak-256 hash of `data` as a word. Dynamically-sized data is stored from the hash of its slot
+pub fn keccak(data: &[u8]) -> U256 {
+    U256::from_big_endian(&keccak256(data))
+}
+
+/// The slot of the value that `key` maps to, in a mapping stored at `slot`.
+/// Value-type keys are padded to a word, `bytes` and `string` keys are not
+pub fn mapping_slot(key: &[u8], slot: U256) -> U256 {
+    let mut data = key.to_vec();
+    data.extend_from_slice(&be_bytes(slot));
+    keccak(&data)
+}
+
+// bytes a value type takes up when packed into a slot. Reference types are never packed
+fn packed_size(ty: &VarType) -> Option<usize> {
+    match ty {
+        VarType::Uint(bits) | VarType::Int(bits) => Some((bits / 8).max(1).min(32)),
+        VarType::Address | VarType::Contract(_) => Some(20),
+        VarType::Bool | VarType::Enum(_) => Some(1),
+        VarType::FixedBytes(len) => Some((*len).max(1).min(32)),
+        _ => None
+    }
+}
+
+// `bytes` and `string` shorter than 32 bytes are stored in the high-order bytes of the slot, with
+// twice the length in the lowest byte. Longer ones store twice the length plus one in the slot,
+// and the data from the hash of the slot
+fn bytes<F>(slot: U256, read: &F) -> Result<Vec<u8>, Error>
+where
+    F: Fn(U256) -> Result<U256, Error>
+{
+    let word = read(slot)?;
+    if !word.bit(0) {
+        let len = (word.low_u64() & 0xff) as usize / 2;
+        return Ok(be_bytes(word)[..len.min(31)].to_vec());
+    }
+    let len = ((word - U256::one()) / U256::from(2)).min(U256::from(MAX_ELEMENTS * 32)).as_u64() as usize;
+    let data = keccak(&be_bytes(slot));
+    let mut bytes = Vec::with_capacity(len);
+    for i in 0..(len + 31) / 32 {
+        bytes.extend_from_slice(&be_bytes(read(data + U256::from(i))?));
+    }
+    bytes.truncate(len);
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculate::speculate;
+
+    speculate! {
+        it "reads short and long strings" {
+            let short = |_| Ok(U256::from_big_endian(b"hello") << (27 * 8) | U256::from(10));
+            assert_eq!(bytes(U256::zero(), &short).unwrap(), b"hello".to_vec());
+
+            let long = |slot: U256| if slot.is_zero() {
+                Ok(U256::from(40 * 2 + 1))
+            } else {
+                Ok(!U256::zero())
+            };
+            assert_eq!(bytes(U256::zero(), &long).unwrap(), vec![0xff; 40]);
+        }
+
+        it "derives the slot of a mapping's value" {
+            // keccak(pad32(0) . pad32(0))
+            let slot = mapping_slot(&[0u8; 32], U256::zero());
+            assert_eq!(format!("{:x}", slot), "ad3228b676f7d3cd4284a5443f17f1962b36e491b30a40b2405849e597ba5fb5");
+        }
+    }
+}
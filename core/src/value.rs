@@ -0,0 +1,176 @@
+//! Values of variables, decoded from the words and memory of the EVM
+use std::fmt;
+use ethereum_types::{Address, U256};
+use edb_compiler::{VarType, Location};
+
+/// The most elements of an array that are decoded
+pub(crate) const MAX_ELEMENTS: usize = 256;
+
+/// A decoded value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Uint(U256),
+    /// A signed integer of `bits` bits, in two's complement
+    Int(U256, usize),
+    Address(Address),
+    Bool(bool),
+    FixedBytes(Vec<u8>),
+    Bytes(Vec<u8>),
+    String(String),
+    Array(Vec<Value>),
+    /// Members of a struct, by name
+    Struct(Vec<(String, Value)>),
+    /// A reference that could not be followed, to the location it points to
+    Pointer(Location, U256),
+    /// A word of a type that cannot be decoded
+    Raw(U256),
+}
+
+impl Value {
+    /// Decode a value type from the word that holds it
+    pub fn from_word(ty: &VarType, word: U256) -> Value {
+        match ty {
+            VarType::Uint(bits) => Value::Uint(word & mask(*bits)),
+            VarType::Int(bits) => Value::Int(word & mask(*bits), *bits),
+            VarType::Enum(_) => Value::Uint(word & mask(8)),
+            VarType::Address | VarType::Contract(_) => Value::Address(address(word)),
+            VarType::Bool => Value::Bool(!word.is_zero()),
+            // fixed bytes are left-aligned
+            VarType::FixedBytes(len) => Value::FixedBytes(be_bytes(word)[..(*len).min(32)].to_vec()),
+            _ => Value::Raw(word),
+        }
+    }
+
+    /// Decode a variable held in a word on the stack.
+    /// References into memory are followed
+    pub fn from_stack(ty: &VarType, location: Option<Location>, word: U256, memory: &[u8]) -> Value {
+        match location {
+            Some(Location::Memory) => Self::from_memory(ty, word, memory),
+            Some(location) => Value::Pointer(location, word),
+            None => Self::from_word(ty, word),
+        }
+    }
+
+    /// Decode a reference type that starts at `ptr` in memory
+    pub fn from_memory(ty: &VarType, ptr: U256, memory: &[u8]) -> Value {
+        let offset = match as_offset(ptr, memory) {
+            Some(o) => o,
+            None => return Value::Pointer(Location::Memory, ptr)
+        };
+        match ty {
+            // length-prefixed
+            VarType::Bytes | VarType::String => {
+                let len = as_offset(read_word(memory, offset), memory).unwrap_or(0);
+                let start = (offset + 32).min(memory.len());
+                let bytes = memory[start..(start + len).min(memory.len())].to_vec();
+                match ty {
+                    VarType::String => Value::String(String::from_utf8_lossy(&bytes).into_owned()),
+                    _ => Value::Bytes(bytes)
+                }
+            },
+            VarType::Array(elem, len) => {
+                // dynamic arrays are length-prefixed
+                let (len, start) = match len {
+                    Some(len) => (*len, offset),
+                    None => (as_offset(read_word(memory, offset), memory).unwrap_or(0), offset + 32)
+                };
+                let elements = (0..len.min(MAX_ELEMENTS))
+                    .map(|i| {
+                        let word = read_word(memory, start + i * 32);
+                        if elem.is_reference() {
+                            Self::from_memory(elem, word, memory)
+                        } else {
+                            Self::from_word(elem, word)
+                        }
+                    })
+                    .collect();
+                Value::Array(elements)
+            },
+            _ => Value::Pointer(Location::Memory, ptr)
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Uint(v) => write!(f, "{}", v),
+            Value::Int(v, bits) => {
+                if *bits > 0 && v.bit(*bits - 1) {
+                    // negate the two's complement within `bits`
+                    let magnitude = ((!*v) & mask(*bits)) + U256::one();
+                    write!(f, "-{}", magnitude)
+                } else {
+                    write!(f, "{}", v)
+                }
+            },
+            Value::Address(a) => write!(f, "{:#x}", a),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::FixedBytes(b) | Value::Bytes(b) => write!(f, "0x{}", hex(b)),
+            Value::String(s) => write!(f, "{:?}", s),
+            Value::Array(elements) => {
+                write!(f, "[")?;
+                for (i, e) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", e)?;
+                }
+                write!(f, "]")
+            },
+            Value::Struct(members) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", name, value)?;
+                }
+                write!(f, "}}")
+            },
+            Value::Pointer(location, ptr) => write!(f, "<{:?} {:#x}>", location, ptr),
+            Value::Raw(word) => write!(f, "{:#x}", word),
+        }
+    }
+}
+
+// the lowest `bits` bits set
+pub(crate) fn mask(bits: usize) -> U256 {
+    if bits >= 256 {
+        !U256::zero()
+    } else {
+        (U256::one() << bits) - U256::one()
+    }
+}
+
+pub(crate) fn be_bytes(word: U256) -> [u8; 32] {
+    let mut buf = [0u8; 32];
+    word.to_big_endian(&mut buf);
+    buf
+}
+
+fn address(word: U256) -> Address {
+    Address::from(&be_bytes(word)[12..])
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// an offset into memory, if it is in bounds
+fn as_offset(word: U256, memory: &[u8]) -> Option<usize> {
+    if word > U256::from(memory.len()) {
+        None
+    } else {
+        Some(word.as_u64() as usize)
+    }
+}
+
+// memory beyond the end reads as zero
+fn read_word(memory: &[u8], offset: usize) -> U256 {
+    let mut buf = [0u8; 32];
+    for (i, b) in buf.iter_mut().enumerate() {
+        *b = *memory.get(offset + i).unwrap_or(&0);
+    }
+    U256::from_big_endian(&buf)
+}
@@ -5,8 +5,11 @@ mod types;
 
 pub use self::types::{Mode, LogLevel};
 use super::lib::File;
+use std::path::PathBuf;
 use failure::Error;
 use ethereum_types::Address;
+use edb_core::FoundationVersion;
+use web3::types::{BlockId, H256};
 
 pub struct Configuration {
     pub file: File,
@@ -14,6 +17,12 @@ pub struct Configuration {
     contract: Option<String>,
     mode: Mode,
     address: Address,
+    fork: FoundationVersion,
+    block: BlockId,
+    tx: Option<H256>,
+    record: Option<PathBuf>,
+    replay: Option<PathBuf>,
+    state_cache: Option<PathBuf>,
 }
 
 impl Configuration {
@@ -42,6 +51,36 @@ impl Configuration {
     pub fn addr(&self) -> &Address {
         &self.address
     }
+
+    /// The hard fork to compile and emulate with
+    pub fn fork(&self) -> &FoundationVersion {
+        &self.fork
+    }
+
+    /// The block to read account state from
+    pub fn block(&self) -> &BlockId {
+        &self.block
+    }
+
+    /// A mined transaction to debug on startup
+    pub fn tx(&self) -> Option<&H256> {
+        self.tx.as_ref()
+    }
+
+    /// A file to record the responses of the node to
+    pub fn record(&self) -> Option<&PathBuf> {
+        self.record.as_ref()
+    }
+
+    /// A recorded session to debug instead of a node
+    pub fn replay(&self) -> Option<&PathBuf> {
+        self.replay.as_ref()
+    }
+
+    /// A file to keep the account state read from the node in between sessions
+    pub fn state_cache(&self) -> Option<&PathBuf> {
+        self.state_cache.as_ref()
+    }
 }
 
 
@@ -7,9 +7,12 @@ use log::*;
 use http::uri::Uri;
 use failure::Error;
 use clap::{App, load_yaml, value_t};
-use ethereum_types::H160;
+use ethereum_types::{H160, H256};
+use web3::types::{BlockId, BlockNumber};
+use edb_core::FoundationVersion;
 
 use super::types::*;
+use super::err::ConfigurationError;
 
 pub struct CLIArgs {
     pub file: PathBuf,
@@ -18,6 +21,12 @@ pub struct CLIArgs {
     pub contract: Option<String>,
     pub log_level: LogLevel,
     pub address: H160,
+    pub fork: FoundationVersion,
+    pub block: BlockId,
+    pub tx: Option<H256>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub state_cache: Option<PathBuf>,
 }
 
 pub fn parse() -> Result<CLIArgs, Error> {
@@ -37,9 +46,52 @@ pub fn parse() -> Result<CLIArgs, Error> {
         warn!("No RPC mode specified, using default 'TUI'");
         Mode::default()
     });
-    let transport = matches.value_of("rpc").expect("Must specify an RPC to use");
+    let transport = matches.value_of("rpc").unwrap_or("http://localhost:8545");
     let transport = http::uri::Uri::from_shared(transport.as_bytes().into())?;
+    let fork = match matches.value_of("hardfork") {
+        Some(f) => f.parse()?,
+        None => FoundationVersion::default()
+    };
+
+    let block = match matches.value_of("block") {
+        Some(b) => parse_block(b)?,
+        None => BlockId::Number(BlockNumber::Latest)
+    };
+
+    let tx = match matches.value_of("tx") {
+        Some(t) => Some(H256::from_str(t.trim_start_matches("0x")).map_err(|_| ConfigurationError::InputError(format!("Invalid transaction hash `{}`", t)))?),
+        None => None
+    };
 
-    Ok(CLIArgs { file, mode, transport, contract, log_level, address })
+    let record = matches.value_of("record").map(PathBuf::from);
+    let replay = matches.value_of("replay").map(PathBuf::from);
+    let state_cache = matches.value_of("state-cache").map(PathBuf::from);
+
+    Ok(CLIArgs { file, mode, transport, contract, log_level, address, fork, block, tx, record, replay, state_cache })
+}
+
+// a block is a 32-byte hash, a number in decimal or `0x` prefixed hexadecimal, or one of
+// `latest`, `earliest` and `pending`
+fn parse_block(block: &str) -> Result<BlockId, ConfigurationError> {
+    let invalid = || ConfigurationError::InputError(format!("Invalid block `{}`", block));
+    let number = match block.to_ascii_lowercase().as_str() {
+        "latest" => BlockNumber::Latest,
+        "earliest" => BlockNumber::Earliest,
+        "pending" => BlockNumber::Pending,
+        other if other.starts_with("0x") => {
+            let stripped = &other[2..];
+            if stripped.len() == 64 {
+                let hash = H256::from_str(stripped).map_err(|_| ConfigurationError::InputError(format!("Invalid block hash `{}`", block)))?;
+                return Ok(BlockId::Hash(hash));
+            }
+            BlockNumber::Number(u64::from_str_radix(stripped, 16).map_err(|_| invalid())?)
+        },
+        other if other.len() == 64 => {
+            let hash = H256::from_str(other).map_err(|_| ConfigurationError::InputError(format!("Invalid block hash `{}`", block)))?;
+            return Ok(BlockId::Hash(hash));
+        },
+        other => BlockNumber::Number(other.parse().map_err(|_| invalid())?)
+    };
+    Ok(BlockId::Number(number))
 }
 
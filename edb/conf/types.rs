@@ -55,7 +55,13 @@ impl From<CLIArgs> for Configuration {
             contract: args.contract,
             transport: args.transport,
             mode: args.mode,
-            address: args.address
+            address: args.address,
+            fork: args.fork,
+            block: args.block,
+            tx: args.tx,
+            record: args.record,
+            replay: args.replay,
+            state_cache: args.state_cache,
         }
     }
 }
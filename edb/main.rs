@@ -4,7 +4,7 @@ mod lib;
 mod err;
 //mod rpc;
 
-use edb_core::{Transport, Language, Solidity};
+use edb_core::{Transport, Recorder, Replay, StateCache, Language, Solidity, Patch, FoundationVersion, FrontierPatch, HomesteadPatch, SpuriousDragonPatch, ByzantiumPatch};
 use self::{
     conf::Mode,
     shell::Shell,
@@ -31,6 +31,11 @@ fn main() -> Result<(), Error> {
     // Take care of the 'Transport' Generic based on CLI Arguments
     match *conf.file.file_type() {
         FileType::Solidity => {
+            if let Some(session) = conf.replay().cloned() {
+                info!("Replaying session {}", session.display());
+                let lang = Solidity::new(conf.fork().clone());
+                return start_provider(conf, web3::Web3::new(Replay::open(session)?), lang);
+            }
             let trans = conf.transport().clone();
             debug!("Transport {}", into_str(trans.clone()));
             match trans.scheme_part().map(|s| s.as_str()) {
@@ -40,8 +45,7 @@ fn main() -> Result<(), Error> {
                             error!("{}", e);
                             std::process::exit(1);
                         });
-                    let client = web3::Web3::new(http);
-                    start_provider(conf, client, Solidity::default())?;
+                    start_transport(conf, http)?;
                 },
                 Some("file") => {
                     // TODO: This probably won't work. IPC transport expects a normal filepath
@@ -50,8 +54,7 @@ fn main() -> Result<(), Error> {
                             error!("{}", e);
                             std::process::exit(1);
                         });
-                    let client = web3::Web3::new(ipc);
-                    start_provider(conf, client, Solidity::default())?;
+                    start_transport(conf, ipc)?;
                 },
                 Some("ws") => {
                     let (_eloop, ws) = web3::transports::WebSocket::new(into_str(trans).as_str())
@@ -59,8 +62,7 @@ fn main() -> Result<(), Error> {
                             error!("{}", e);
                             std::process::exit(1);
                         });
-                    let client = web3::Web3::new(ws);
-                    start_provider(conf, client, Solidity::default())?;
+                    start_transport(conf, ws)?;
                 },
                 None => {
                     error!("Must provide scheme of URI for eth RPC");
@@ -80,11 +82,52 @@ fn main() -> Result<(), Error> {
     Ok(())
 }
 
+// Cache the state read from the node, if asked to
+fn start_transport<T>(conf: conf::Configuration, transport: T) -> Result<(), Error> where T: Transport, T::Out: 'static {
+    match conf.state_cache().cloned() {
+        Some(path) => {
+            info!("Caching state in {}", path.display());
+            start_recording(conf, StateCache::new(transport, path)?)
+        },
+        None => start_recording(conf, transport)
+    }
+}
+
+// Record the responses of the node, if asked to
+fn start_recording<T>(conf: conf::Configuration, transport: T) -> Result<(), Error> where T: Transport, T::Out: 'static {
+    let lang = Solidity::new(conf.fork().clone());
+    match conf.record().cloned() {
+        Some(path) => {
+            info!("Recording session to {}", path.display());
+            start_provider(conf, web3::Web3::new(Recorder::new(transport, path)?), lang)
+        },
+        None => start_provider(conf, web3::Web3::new(transport), lang)
+    }
+}
+
+// Take care of the 'Patch' Generic based on the hard fork
 fn start_provider<T>(conf: conf::Configuration, client: web3::Web3<T>, lang: impl Language)
     -> Result<(), Error> where T: Transport
+{
+    match *conf.fork() {
+        FoundationVersion::Frontier => start_mode::<T, FrontierPatch, _>(conf, client, lang),
+        FoundationVersion::Homestead => start_mode::<T, HomesteadPatch, _>(conf, client, lang),
+        FoundationVersion::SpuriousDragon => start_mode::<T, SpuriousDragonPatch, _>(conf, client, lang),
+        FoundationVersion::Byzantium => start_mode::<T, ByzantiumPatch, _>(conf, client, lang),
+    }
+}
+
+fn start_mode<T, P, L>(conf: conf::Configuration, client: web3::Web3<T>, lang: L)
+    -> Result<(), Error> where T: Transport, P: Patch, L: Language
 {
     match *conf.mode() {
-        Mode::Tui => Shell::<T>::new(lang, client, conf.addr().clone(), conf.file().clone())?.run()?,
+        Mode::Tui => {
+            let mut shell = Shell::<T, P>::new(lang, client, *conf.addr(), conf.file().clone(), conf.block().clone())?;
+            if let Some(tx) = conf.tx() {
+                shell.replay(*tx, conf.contract().map(|c| c.as_str()))?;
+            }
+            shell.run()?
+        },
         Mode::Rpc => unimplemented!(), //rpc::Rpc::<T>::new(lang, client, conf.addr().clone(), conf.file().clone())?.run()?,
     }
     Ok(())
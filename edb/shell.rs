@@ -9,30 +9,35 @@ mod err;
 
 use failure::Error;
 use log::*;
-use ethereum_types::Address;
+use ethereum_types::{Address, H256};
+use web3::types::{BlockId, BlockNumber};
 use termion::{
     input::TermRead,
     event::Key,
     raw::IntoRawMode,
 };
 
-use std::io::{stdin, stdout, Write};
+use std::{io::{stdin, stdout, Write}, collections::HashMap};
 
-use edb_core::{Debugger, Language, Transport, CompiledFiles};
+use edb_core::{Debugger, Language, Transport, CompiledFiles, Patch, StateOverride};
 
 use self::commands::Command;
 use self::ops::*;
 use self::err::*;
 use super::lib::File;
 
-pub struct Shell<T> where T: Transport {
+pub struct Shell<T, P> where T: Transport, P: Patch {
     shell_history: Vec<String>,
-    dbg: Option<Debugger<T>>,
+    dbg: Option<Debugger<T, P>>,
     files: CompiledFiles, // TODO combine files with File struct
     client: web3::Web3<T>,
     addr: Address,
     root_file: File,
-    current: Option<Vec<String>>
+    // block every session reads state from
+    block: BlockNumber,
+    // state every session runs against instead of what the node holds
+    overrides: HashMap<Address, StateOverride>,
+    current: Option<(Command, Vec<String>)>
 }
 macro_rules! check {
     ($dbg:expr, $cmd: stmt) =>  ({
@@ -51,10 +56,13 @@ macro_rules! check {
 // a simple shell
 // Does nothing on no user input, will only crash with really fatal errors
 // otherwise errors which are fixable are printed
-impl<T> Shell<T> where T: Transport {
+impl<T, P> Shell<T, P> where T: Transport, P: Patch {
 
-    pub fn new<L>(lang: L, client: web3::Web3<T>, addr: Address, file: File) -> Result<Self, Error> where L: Language {
+    pub fn new<L>(lang: L, client: web3::Web3<T>, addr: Address, file: File, block: BlockId) -> Result<Self, Error> where L: Language {
         debug!("File: {:?}", file);
+        // pin the block so that every session replays against the same state
+        let block = helpers::resolve_block(&client, block)?;
+        info!("Reading state from block {:?}", block);
         Ok(Self {
             shell_history: Vec::new(),
             dbg: None,
@@ -62,10 +70,19 @@ impl<T> Shell<T> where T: Transport {
             client, 
             addr, 
             root_file: file,
+            block,
+            overrides: HashMap::new(),
             current: None
         })
     }
 
+    /// Start the shell with a session already open on the mined transaction `hash`, running
+    /// `contract` if it is named
+    pub fn replay(&mut self, hash: H256, contract: Option<&str>) -> Result<(), Error> {
+        let hash = format!("{:#x}", hash);
+        self.commands(Command::DebugTx, Some(hash.as_str()).into_iter().chain(contract))
+    }
+
     pub fn run(mut self) -> Result<(), Error> {
         welcome();
 
@@ -85,6 +102,12 @@ impl<T> Shell<T> where T: Transport {
                         Command::None
                     },
                 };
+                // return instead of exiting, so that sessions and state being recorded are saved
+                // as the transport is dropped
+                if command == Command::Quit {
+                    quit();
+                    break 'shell;
+                }
 
                 match self.commands(command, parts) {
                     Ok(_)  => (),
@@ -94,6 +117,7 @@ impl<T> Shell<T> where T: Transport {
                 }
             } // do nothing on no input
         }
+        Ok(())
     }
 
     // parse events first, then if no events add key to buffer
@@ -148,48 +172,99 @@ impl<T> Shell<T> where T: Transport {
         Ok(())
     }
 
+    // start a new session with the state overrides, keeping the breakpoints and coverage of the one
+    // it replaces
+    fn open(&mut self, mut dbg: Debugger<T, P>) -> Result<(), Error> {
+        for (address, state) in self.overrides.iter() {
+            dbg.override_state(*address, state)?;
+        }
+        if let Some(mut old) = self.dbg.take() {
+            if let Some(coverage) = old.take_coverage() {
+                dbg.record_coverage(coverage);
+            }
+            dbg.restore_breakpoints(old.into_breakpoints());
+        }
+        self.dbg.replace(dbg);
+        Ok(())
+    }
+
     // TODO: unecessary cloning
     fn commands<'a>(&mut self, command: Command, mut args: impl Iterator<Item = &'a str> + Clone) -> Result<(), Error> {
         
         match command {
             Command::Help    => help(args.next())?,
             Command::Clear   => clear()?,
+            // `set stack|mem|storage` changes the paused state, rather than setting up a transaction
+            Command::Set if args.clone().next().map(|a| ["stack", "mem", "memory", "storage"].contains(&a)).unwrap_or(false) => {
+                check!(self.dbg, set_state(&mut self.dbg.as_mut().unwrap(), args)?)
+            },
             Command::Set     => {
                 let a_c = args.clone();
-                self.current = Some(a_c.map(|s| s.to_string()).collect::<Vec<String>>());
-                let dbg = set(args, &self.root_file, self.files.clone(), self.addr.clone(), self.client.clone())?;
-                self.dbg.replace(dbg);
+                self.current = Some((Command::Set, a_c.map(|s| s.to_string()).collect::<Vec<String>>()));
+                let dbg = set(args, &self.root_file, self.files.clone(), self.addr, self.client.clone(), self.block)?;
+                self.open(dbg)?;
+            },
+            Command::Create  => {
+                let a_c = args.clone();
+                self.current = Some((Command::Create, a_c.map(|s| s.to_string()).collect::<Vec<String>>()));
+                let dbg = create(args, &self.root_file, self.files.clone(), self.client.clone(), self.block)?;
+                self.open(dbg)?;
+            },
+            Command::DebugTx => {
+                let a_c = args.clone();
+                self.current = Some((Command::DebugTx, a_c.map(|s| s.to_string()).collect::<Vec<String>>()));
+                let dbg = debug_tx(args, &self.root_file, self.files.clone(), self.client.clone())?;
+                self.open(dbg)?;
             },
             Command::Run => {
                 check!(self.dbg);
                 let dbg = self.dbg.as_mut().unwrap();
                 dbg.run()?;
+                stopped(dbg);
             }
             Command::Reset   => {
                 if self.current.is_none() {
                     return Err(ShellError::Custom("Must run before you can reset".to_string()).into());
                 }
-                let current = self.current.as_mut().unwrap();
-                let dbg = set(current.iter().map(|s| s.as_str()), &self.root_file, self.files.clone(), self.addr.clone(), self.client.clone())?;
-                self.dbg.replace(dbg);
+                let (command, current) = self.current.as_ref().unwrap();
+                let dbg = match command {
+                    Command::Create => create(current.iter().map(|s| s.as_str()), &self.root_file, self.files.clone(), self.client.clone(), self.block)?,
+                    Command::DebugTx => debug_tx(current.iter().map(|s| s.as_str()), &self.root_file, self.files.clone(), self.client.clone())?,
+                    _ => set(current.iter().map(|s| s.as_str()), &self.root_file, self.files.clone(), self.addr, self.client.clone(), self.block)?
+                };
+                self.open(dbg)?;
             },
             Command::Chain   => { 
                 if self.dbg.is_none() {
                     return Err(ShellError::Custom("Must run before you can reset".to_string()).into());
                 }
-                self.current = Some(args.clone().map(|s| s.to_string()).collect::<Vec<String>>());
+                self.current = Some((Command::Chain, args.clone().map(|s| s.to_string()).collect::<Vec<String>>()));
                 chain(&mut self.dbg.as_mut().unwrap(), self.files.clone(), args, &self.client, self.addr.clone())?;
             },
             Command::Finish  => finish(),
             Command::Step    => check!(self.dbg, step(&mut self.dbg.as_mut().unwrap(), args.next(), args.next())?),
-            Command::Break   => br(&mut self.dbg.as_mut().unwrap(), args.next())?,
+            Command::StepInstruction | Command::NextInstruction => {
+                check!(self.dbg, step_instruction(&mut self.dbg.as_mut().unwrap(), command, args.next())?)
+            },
+            Command::Break   => check!(self.dbg, br(&mut self.dbg.as_mut().unwrap(), args)?),
+            Command::Watch   => check!(self.dbg, watch(&mut self.dbg.as_mut().unwrap(), args)?),
+            Command::Delete | Command::Enable | Command::Disable => {
+                check!(self.dbg, toggle_breakpoint(&mut self.dbg.as_mut().unwrap(), command, args.next())?)
+            },
+            Command::Ignore  => check!(self.dbg, ignore(&mut self.dbg.as_mut().unwrap(), args.next(), args.next())?),
+            Command::Info    => check!(self.dbg, info(&self.dbg.as_ref().unwrap(), args.next())?),
             Command::Next    => check!(self.dbg, next(&mut self.dbg.as_mut().unwrap())?),
             Command::Execute => execute(),
-            Command::Print   => check!(self.dbg, print(&mut self.dbg.as_mut().unwrap(), args.next(), args.next())?),
+            Command::Print   => check!(self.dbg, print(&mut self.dbg.as_mut().unwrap(), args)?),
+            Command::Backtrace => check!(self.dbg, backtrace(&self.dbg.as_ref().unwrap())?),
+            Command::Profile => check!(self.dbg, profile(&mut self.dbg.as_mut().unwrap(), args.next())?),
+            Command::Coverage => check!(self.dbg, coverage(&mut self.dbg.as_mut().unwrap(), args.next())?),
+            Command::Trace   => check!(self.dbg, trace(&mut self.dbg.as_mut().unwrap(), args.next())?),
+            Command::Override => override_state(self.dbg.as_mut(), &mut self.overrides, args)?,
             // Command::Stack   => stack(),
             // Command::Memory  => memory(),
             // Command::Storage => storage(),
-            Command::Opcode  => opcode(),
+            Command::Opcode  => check!(self.dbg, opcode(&self.dbg.as_ref().unwrap(), args.next())?),
             Command::Quit    => quit(),
             Command::None    => (),
         };
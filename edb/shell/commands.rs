@@ -8,19 +8,34 @@ pub enum Command {
     Help, // help message
     Clear, // Clear the screen
     Set, // set the params
+    Create, // set the params for a contract creation
+    DebugTx, // debug a transaction that has already been mined
     Run, // run a function
     Reset, // reset to first breakpoint
     Chain, // chain another tx
     Finish, // Finish current program (Next transaction keeps state from last transaction)
     Step, // step to next line
+    StepInstruction, // step a single instruction
+    NextInstruction, // step a single instruction, running any call it makes
     Break, // toggle breakpoint
+    Watch, // stop when storage or memory is written
+    Delete, // delete a breakpoint
+    Enable, // enable a breakpoint
+    Disable, // disable a breakpoint
+    Ignore, // pass a breakpoint without stopping
+    Info, // show information about the debugger, ie breakpoints
     Next, // go to next breakpoint
     Execute, // Execute to end (does not keep state from last transaction)
     Print, // Print variables n' stuff
+    Backtrace, // print the call stack
+    Profile, // measure the gas used by each line
+    Coverage, // record which lines are executed
+    Trace, // write a trace of every instruction
+    Override, // run against modified state
     // Stack, // Show a representation the stack
     // Memory, // Show a representation of the memory
     // Storage, // show a representation of the storage
-    Opcode, // show the current opcode
+    Opcode, // disassemble the code around the current instruction
     Quit, // quit the debugger
     None, // no command
 }
@@ -31,15 +46,30 @@ impl From<&Command> for String {
             Command::Help    => String::from("help"),
             Command::Clear   => String::from("clear"),
             Command::Set     => String::from("set"),
+            Command::Create  => String::from("create"),
+            Command::DebugTx => String::from("debug-tx"),
             Command::Run     => String::from("run"),
             Command::Reset   => String::from("reset"),
             Command::Chain   => String::from("chain"),
             Command::Finish  => String::from("finish"),
             Command::Step    => String::from("step"),
+            Command::StepInstruction => String::from("stepi"),
+            Command::NextInstruction => String::from("nexti"),
             Command::Break   => String::from("break"),
+            Command::Watch   => String::from("watch"),
+            Command::Delete  => String::from("delete"),
+            Command::Enable  => String::from("enable"),
+            Command::Disable => String::from("disable"),
+            Command::Ignore  => String::from("ignore"),
+            Command::Info    => String::from("info"),
             Command::Next    => String::from("next"),
             Command::Execute => String::from("execute"),
             Command::Print   => String::from("print"),
+            Command::Backtrace => String::from("backtrace"),
+            Command::Profile => String::from("profile"),
+            Command::Coverage => String::from("coverage"),
+            Command::Trace   => String::from("trace"),
+            Command::Override => String::from("override"),
             // Command::Stack   => String::from("stack"),
             // Command::Memory  => String::from("memory"),
             // Command::Storage => String::from("storage"),
@@ -56,15 +86,30 @@ impl From<Command> for String {
             Command::Help    => String::from("help"),
             Command::Clear   => String::from("clear"),
             Command::Set     => String::from("set"),
+            Command::Create  => String::from("create"),
+            Command::DebugTx => String::from("debug-tx"),
             Command::Run     => String::from("run"),
             Command::Reset   => String::from("reset"),
             Command::Chain   => String::from("chain"),
             Command::Finish  => String::from("finish"),
             Command::Step    => String::from("step"),
+            Command::StepInstruction => String::from("stepi"),
+            Command::NextInstruction => String::from("nexti"),
             Command::Break   => String::from("break"),
+            Command::Watch   => String::from("watch"),
+            Command::Delete  => String::from("delete"),
+            Command::Enable  => String::from("enable"),
+            Command::Disable => String::from("disable"),
+            Command::Ignore  => String::from("ignore"),
+            Command::Info    => String::from("info"),
             Command::Next    => String::from("next"),
             Command::Execute => String::from("execute"),
             Command::Print   => String::from("print"),
+            Command::Backtrace => String::from("backtrace"),
+            Command::Profile => String::from("profile"),
+            Command::Coverage => String::from("coverage"),
+            Command::Trace   => String::from("trace"),
+            Command::Override => String::from("override"),
             // Command::Stack   => String::from("stack"),
             // Command::Memory  => String::from("memory"),
             // Command::Storage => String::from("storage"),
@@ -84,19 +129,34 @@ impl FromStr for Command {
             "help"|"?"        => Ok(Command::Help),
             "clear"           => Ok(Command::Clear),
             "set"             => Ok(Command::Set),
+            "create"          => Ok(Command::Create),
+            "debug-tx"        => Ok(Command::DebugTx),
             "run"             => Ok(Command::Run),
             "reset"           => Ok(Command::Reset),
             "chain"           => Ok(Command::Chain),
             "finish"          => Ok(Command::Finish),
             "step"            => Ok(Command::Step),
-            "break"           => Ok(Command::Break),
+            "stepi"|"si"      => Ok(Command::StepInstruction),
+            "nexti"|"ni"      => Ok(Command::NextInstruction),
+            "break"|"b"       => Ok(Command::Break),
+            "watch"|"w"       => Ok(Command::Watch),
+            "delete"|"d"      => Ok(Command::Delete),
+            "enable"          => Ok(Command::Enable),
+            "disable"         => Ok(Command::Disable),
+            "ignore"          => Ok(Command::Ignore),
+            "info"|"i"        => Ok(Command::Info),
             "next"            => Ok(Command::Next),
             "execute"|"exec"  => Ok(Command::Execute),
             "print"           => Ok(Command::Print),
+            "backtrace"|"bt"  => Ok(Command::Backtrace),
+            "profile"|"prof"  => Ok(Command::Profile),
+            "coverage"|"cov"  => Ok(Command::Coverage),
+            "trace"           => Ok(Command::Trace),
+            "override"        => Ok(Command::Override),
             // "stack"           => Ok(Command::Stack),
             // "memory"|"mem"    => Ok(Command::Memory),
             // "storage"|"storg" => Ok(Command::Storage),
-            "opcode"|"op"     => Ok(Command::Opcode),
+            "opcode"|"op"|"disas" => Ok(Command::Opcode),
             "quit"|"exit"     => Ok(Command::Quit),
             _ => Err(ShellError::CommandNotFound(s.to_string()).into())
         }
@@ -1,9 +1,9 @@
 use std::rc::Rc;
 
-use sputnikvm::{HeaderParams, ValidTransaction, TransactionAction};
+use sputnikvm::{ValidTransaction, TransactionAction};
 use edb_core::Transport;
 use ethereum_types::{Address, U256};
-use web3::types::{BlockNumber, BlockId};
+use web3::types::{BlockNumber, BlockId, H256, TransactionId};
 use futures::future::Future;
 use ethabi::{ParamType, Token};
 
@@ -29,10 +29,22 @@ macro_rules! simple_err {
     })
 }
 
-pub fn parse_args<'a>(func: &str, abi: &edb_core::Contract, mut args: impl Iterator<Item=&'a str>) -> Result<Vec<ethabi::Token>, ShellError> {
+pub fn parse_args<'a>(func: &str, abi: &edb_core::Contract, args: impl Iterator<Item=&'a str>) -> Result<Vec<ethabi::Token>, ShellError> {
     let func = abi.function(func).map_err(|e| ShellError::Custom(format!("{}", e.description())))?;
+    parse_params(func.inputs.as_slice(), args)
+}
+
+/// parse the arguments to a contracts' constructor
+pub fn parse_constructor_args<'a>(abi: &edb_core::Contract, args: impl Iterator<Item=&'a str>) -> Result<Vec<ethabi::Token>, ShellError> {
+    match abi.constructor() {
+        Some(constructor) => parse_params(constructor.inputs.as_slice(), args),
+        None => Ok(Vec::new())
+    }
+}
+
+fn parse_params<'a>(params: &[ethabi::Param], mut args: impl Iterator<Item=&'a str>) -> Result<Vec<ethabi::Token>, ShellError> {
     let mut param_tokens = Vec::new(); 
-    for param in func.inputs.iter() {
+    for param in params.iter() {
         match &param.kind {
             ParamType::Address => {
                 let arg = args.next();
@@ -96,23 +108,38 @@ pub fn to_num() -> () {
 }
 
 
-// TODO: do proper error handling
-// TODO: hide bigint/sputnikvm types. they clutter w/ ethereum types. Should not be needed when
-// creating providers like shells/rpcs/daemons
-// TODO: Edge cases not handled here (see Issue #29)
-//
-// just use params from latest block for header
-fn get_headers<T>(client: &web3::Web3<T>) -> HeaderParams where T: Transport {
-    let block = client.eth().block(BlockId::Number(BlockNumber::Latest)).wait().expect("Could not get latest block").expect("Failed getting latest block");
-    let latest = client.eth().block_number().wait().expect("Could not query latest block");
-    
-    HeaderParams {
-        beneficiary: bigint::H160(block.author.0),
-        timestamp: block.timestamp.as_u64(),
-        number: bigint::U256(latest.0),
-        difficulty: bigint::U256(block.difficulty.0),
-        gas_limit: bigint::Gas::from(block.gas_limit.as_u64())
-    }
+/// resolve a block identifier to the number of a mined block
+pub fn resolve_block<T>(client: &web3::Web3<T>, block: BlockId) -> Result<BlockNumber, ShellError> where T: Transport {
+    let mined = client.eth()
+        .block(block.clone())
+        .wait()
+        .map_err(|e| ShellError::Custom(format!("{}", e)))?
+        .ok_or_else(|| ShellError::Custom(format!("Block {:?} not found", block)))?;
+    let number = mined.number.ok_or_else(|| ShellError::Custom("Cannot read state from a pending block".to_string()))?;
+    Ok(BlockNumber::Number(number.as_u64()))
+}
+
+/// the compiled contract a mined transaction runs: the one whose code is deployed at the account
+/// the transaction calls, or whose creation code the transaction deploys
+pub fn contract_of<'a, T>(client: &web3::Web3<T>, contracts: &'a [edb_core::Contract], hash: H256)
+    -> Result<&'a edb_core::Contract, ShellError> where T: Transport
+{
+    let tx = client.eth()
+        .transaction(TransactionId::Hash(hash))
+        .wait()
+        .map_err(|e| ShellError::Custom(format!("{}", e)))?
+        .ok_or_else(|| ShellError::Custom(format!("Transaction {:#x} not found", hash)))?;
+    let code = match tx.to {
+        Some(to) => client.eth()
+            .code(to, tx.block_number.map(|n| BlockNumber::Number(n.as_u64())))
+            .wait()
+            .map_err(|e| ShellError::Custom(format!("{}", e)))?
+            .0,
+        None => tx.input.0
+    };
+    contracts.iter()
+        .find(|c| c.code_kind(code.as_slice()).is_some())
+        .ok_or_else(|| ShellError::Custom(format!("No contract matches the code transaction {:#x} runs. Name it with `debug-tx HASH CONTRACT`", hash)))
 }
 
 // TODO: Proper 'transaction' interface that allows for more options (and setting options) like the
@@ -124,7 +151,7 @@ fn get_headers<T>(client: &web3::Web3<T>) -> HeaderParams where T: Transport {
 //
 //
 pub fn create_tx<T>(client: &web3::Web3<T>, addr: Address, abi: &edb_core::Contract, func: &str, params: &[ethabi::Token])
-    -> Result<(HeaderParams, ValidTransaction), ShellError> where T: Transport 
+    -> Result<ValidTransaction, ShellError> where T: Transport 
 {
     let func = abi.function(func)?.encode_input(params)?;
     let acc_zero = get_account(client, 0)?;
@@ -137,7 +164,31 @@ pub fn create_tx<T>(client: &web3::Web3<T>, addr: Address, abi: &edb_core::Contr
         input: Rc::new(func),
         nonce: bigint::U256::zero(),
     };
-    Ok((get_headers(client), tx))
+    Ok(tx)
+}
+
+/// Create a transaction deploying `abi`, with the constructor arguments `params`
+pub fn create_deploy_tx<T>(client: &web3::Web3<T>, abi: &edb_core::Contract, params: &[ethabi::Token], block: BlockNumber)
+    -> Result<ValidTransaction, ShellError> where T: Transport
+{
+    let code = abi.creation_bytecode().to_vec();
+    let input = match abi.constructor() {
+        Some(constructor) => constructor.encode_input(code, params)?,
+        None => code
+    };
+    let acc_zero = get_account(client, 0)?;
+    // the address of the new contract depends on the nonce of the creator
+    let nonce = client.eth().transaction_count(acc_zero, Some(block)).wait().map_err(|e| ShellError::Custom(format!("{}", e)))?;
+    let tx = ValidTransaction {
+        caller: Some(bigint::H160(acc_zero.0)),
+        gas_price: bigint::Gas::one(),
+        gas_limit: bigint::Gas::from(5000000u64),
+        action: TransactionAction::Create,
+        value: bigint::U256::zero(),
+        input: Rc::new(input),
+        nonce: bigint::U256(nonce.0),
+    };
+    Ok(tx)
 }
 
 // TODO:  extend this to also get an account by an ID, not just index
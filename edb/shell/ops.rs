@@ -3,14 +3,16 @@
 use failure::Error;
 use termion::raw::IntoRawMode;
 use ethereum_types::Address;
+use web3::types::{BlockId, BlockNumber, H256};
 use log::*;
 
 use std::{
     io::Write,
     str::{FromStr, SplitWhitespace},
+    collections::HashMap,
 };
 
-use edb_core::{Debugger, CompiledFiles, Transport, contract::Find};
+use edb_core::{Debugger, Coverage, StateOverride, CompiledFiles, Transport, Patch, contract::Find};
 
 use crate::lib::File; // TODO: possibly move file out of configuration.
 use super::commands::Command;
@@ -18,10 +20,19 @@ use super::types::*;
 use super::err::ShellError;
 use super::helpers::{self};
 
+/// lines and functions shown by `profile`
+const PROFILE_ROWS: usize = 20;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Direction {
     Forward,
-    Backward
+    Backward,
+    /// forward, entering any function that is called
+    Into,
+    /// forward, without stopping in functions that are called
+    Over,
+    /// until the current function returns
+    Out,
 }
 
 pub enum Print {
@@ -29,6 +40,12 @@ pub enum Print {
     Storage,
     Stack,
     Memory,
+    Locals,
+    /// event logs the transaction has emitted
+    Logs,
+    Var,
+    /// an expression evaluated in the current frame
+    Expr,
     Forward, // implies lines
     Backward, // implies lines
     Current
@@ -43,6 +60,10 @@ impl FromStr for Print {
             "storage"|"storg" => Ok(Print::Storage),
             "stack"|"s" => Ok(Print::Stack),
             "memory"|"mem" => Ok(Print::Memory),
+            "locals"|"l" => Ok(Print::Locals),
+            "logs"|"events" => Ok(Print::Logs),
+            "var"|"v" => Ok(Print::Var),
+            "expr"|"e" => Ok(Print::Expr),
             "forward"|"f" => Ok(Print::Forward),
             "backward"|"b" => Ok(Print::Backward),
             "current"|"curr" => Ok(Print::Current),
@@ -57,6 +78,9 @@ impl FromStr for Direction {
         match s {
             "forward"|"f" => Ok(Direction::Forward),
             "back"|"backward"|"b" => Ok(Direction::Backward),
+            "in"|"into"|"i" => Ok(Direction::Into),
+            "over"|"o" => Ok(Direction::Over),
+            "out" => Ok(Direction::Out),
             _ => Err(ShellError::DirectionNotFound(s.to_string()).into())
         }
     }
@@ -96,18 +120,34 @@ pub fn help(subcommand: Option<&str>) -> Result<(), Error> {
         match s.parse()? {
             Command::Help    => print!("\nDisplay the help message"),
             Command::Clear   => print!("\nClear the terminal"),
+            Command::Set     => print!("\nSet up a transaction to debug: `set CONTRACT FUNCTION [ARGS]`. While paused, change the state execution continues from: `set stack INDEX VALUE`, counting from the top of the stack, `set mem OFFSET HEX` or `set storage SLOT VALUE`"),
+            Command::Create  => print!("\nDebug the constructor of a contract: `create CONTRACT [ARGS]`"),
+            Command::DebugTx => print!("\nDebug a transaction that has already been mined: `debug-tx HASH [CONTRACT]`. The contract the transaction runs is found by its code if it is not named"),
             Command::Run     => print!("\nRun"),
             Command::Reset   => print!("\nReset"),
             Command::Finish  => print!("\nFinish"),
-            Command::Step    => print!("\nStep"),
-            Command::Break   => print!("\nBreak"),
+            Command::Step    => print!("\nStep: `step [forward|back|in|over|out] [COUNT]`. `in` enters called functions, `over` runs them without stopping, `out` runs until the current function returns"),
+            Command::StepInstruction => print!("\nStep a single EVM instruction, entering any contract it calls: `stepi [COUNT]`. Alias: `si`"),
+            Command::NextInstruction => print!("\nStep a single EVM instruction, running any contract it calls without stopping: `nexti [COUNT]`. Alias: `ni`"),
+            Command::Break   => print!("\nSet a breakpoint: `break [FILE:]LINE [if CONDITION]`. Execution only stops if the condition holds, ie `break Token.sol:24 if balances[msg.sender] > 100`. `break *PC` stops at a byte offset of the code, and `break OPCODE` at every instruction of an opcode, ie `break SSTORE`, or of a family of them, ie `break LOG`"),
+            Command::Watch   => print!("\nStop whenever storage or memory is written: `watch storage SLOT|EXPR [if CONDITION]`, ie `watch storage balances[msg.sender]`, or `watch memory OFFSET LEN [if CONDITION]`. Alias: `w`"),
+            Command::Delete  => print!("\nDelete a breakpoint or watchpoint: `delete ID`"),
+            Command::Enable  => print!("\nEnable a breakpoint: `enable ID`"),
+            Command::Disable => print!("\nDisable a breakpoint without deleting it: `disable ID`"),
+            Command::Ignore  => print!("\nPass a breakpoint the next COUNT times it is hit: `ignore ID COUNT`"),
+            Command::Info    => print!("\nShow information: `info breakpoints`, or `info failures` for every frame that has failed and why"),
             Command::Next    => print!("\nNext"),
             Command::Execute => print!("\nExecute"),
-            Command::Print   => print!("\nPrint vars"),
+            Command::Print   => print!("\nPrint vars: `print [current|forward|backward|stack|memory|storage|locals|logs|result] [COUNT]`, a state variable decoded from storage: `print var NAME`, or the value of an expression: `print balances[msg.sender] + 1`"),
+            Command::Backtrace => print!("\nPrint the call stack, innermost function first. Alias: `bt`"),
+            Command::Profile => print!("\nRun the rest of the transaction, showing the lines and functions that used the most gas: `profile [FILE]`. The whole profile is written to FILE as JSON. Alias: `prof`"),
+            Command::Coverage => print!("\nRecord which lines are executed, in every transaction run from now on: `coverage on`. `coverage FILE` writes the lines executed so far to FILE in LCOV format, and `coverage off` stops recording. Alias: `cov`"),
+            Command::Override => print!("\nRun against modified state: `override balance|nonce ADDRESS VALUE`, `override storage ADDRESS SLOT VALUE`, or `override code ADDRESS FILE` with FILE holding the code in hex. Overrides apply to every session opened after, and to the open one if it has not started"),
+            Command::Trace   => print!("\nRun the rest of the transaction, writing the state before every instruction to FILE as JSON, in the format of geth's `debug_traceTransaction`: `trace FILE`. `reset` first to trace the whole transaction"),
             // Command::Stack   => print!("\nStack"),
             // Command::Memory  => print!("\nMemory"),
             // Command::Storage => print!("\nStorage"),
-            Command::Opcode  => print!("\nOpcode"),
+            Command::Opcode  => print!("\nDisassemble the code around the next instruction, showing the source each instruction maps to: `opcode [COUNT]`. Alias: `disas`"),
             Command::Quit    => print!("\nQuit"),
             _=> (),
         }
@@ -128,12 +168,13 @@ pub fn clear() -> Result<(), Error> {
 // chain
 // need the function ABI to be able to match params
 // pub fn run(contract: &str, func: &str, params: SplitWhitespace) {
-pub fn set<'a, T>(mut params: impl Iterator<Item=&'a str> + Clone, 
+pub fn set<'a, T, P>(mut params: impl Iterator<Item=&'a str> + Clone, 
             file: &File,
             files: CompiledFiles,
             addr: Address, 
-            client: web3::Web3<T>) 
--> Result<Debugger<T>, Error> where T: Transport
+            client: web3::Web3<T>,
+            block: BlockNumber)
+-> Result<Debugger<T, P>, Error> where T: Transport, P: Patch
 {
     let contract = params.next().ok_or(ShellError::Custom("need to specify a contract".to_string()))?;
     let func = params.next().ok_or(ShellError::Custom("Need to specify function to run".to_string()))?;
@@ -150,17 +191,58 @@ pub fn set<'a, T>(mut params: impl Iterator<Item=&'a str> + Clone,
         contract_args = Vec::new();
     }
 
-    let (block, tx) = helpers::create_tx(&client, addr, contract, func, contract_args.as_slice())?;
-    Ok(Debugger::new(file.path(), files.clone(), client.clone(), tx, block, contract.name())?)
+    let tx = helpers::create_tx(&client, addr, contract, func, contract_args.as_slice())?;
+    Ok(Debugger::new(file.path(), files.clone(), client.clone(), tx, BlockId::Number(block), contract.name())?)
+}
+
+/// Debug the creation of a contract
+pub fn create<'a, T, P>(mut params: impl Iterator<Item=&'a str> + Clone,
+            file: &File,
+            files: CompiledFiles,
+            client: web3::Web3<T>,
+            block: BlockNumber)
+-> Result<Debugger<T, P>, Error> where T: Transport, P: Patch
+{
+    let contract = params.next().ok_or_else(|| ShellError::Custom("need to specify a contract".to_string()))?;
+    debug!("Creating {}", contract);
+    let contract = files.contracts().find(contract)?;
+
+    let contract_args = if params.clone().peekable().peek().is_some() {
+        helpers::parse_constructor_args(contract, params)?
+    } else {
+        Vec::new()
+    };
+
+    let tx = helpers::create_deploy_tx(&client, contract, contract_args.as_slice(), block)?;
+    let dbg = Debugger::new(file.path(), files.clone(), client, tx, BlockId::Number(block), contract.name())?;
+    println!("\nDeploying {} to {:#x}", contract.name(), dbg.address());
+    Ok(dbg)
+}
+
+/// Debug a transaction that has already been mined: `debug-tx HASH [CONTRACT]`
+pub fn debug_tx<'a, T, P>(mut params: impl Iterator<Item=&'a str> + Clone,
+            file: &File,
+            files: CompiledFiles,
+            client: web3::Web3<T>)
+-> Result<Debugger<T, P>, Error> where T: Transport, P: Patch
+{
+    let hash = params.next().ok_or_else(|| ShellError::Custom("need to specify a transaction hash".to_string()))?;
+    let hash = H256::from_str(hash.trim_start_matches("0x")).map_err(|_| ShellError::Custom(format!("Invalid transaction hash `{}`", hash)))?;
+    let contract = match params.next() {
+        Some(contract) => files.contracts().find(contract)?,
+        None => helpers::contract_of(&client, files.contracts(), hash)?
+    };
+    debug!("Replaying {:#x} against {}", hash, contract.name());
+    Debugger::replay(file.path(), files.clone(), client, hash, contract.name())
 }
 
 // chain tx to existing debugger
-pub fn chain<'a, T>(dbg: &mut Debugger<T>,
+pub fn chain<'a, T, P>(dbg: &mut Debugger<T, P>,
                     files: CompiledFiles,
                     mut params: impl Iterator<Item=&'a str> + Clone,
                     client: &web3::Web3<T>,
                     addr: Address)
--> Result<(), Error> where T: Transport
+-> Result<(), Error> where T: Transport, P: Patch
 {
     let contract = params.next().ok_or(ShellError::Custom("need to specify a contract".to_string()))?;
     let func = params.next().ok_or(ShellError::Custom("Need to specify a function to run".to_string()))?;
@@ -175,8 +257,9 @@ pub fn chain<'a, T>(dbg: &mut Debugger<T>,
         contract_args = Vec::new();
     }
 
-    let (block, tx) = helpers::create_tx(client, addr, contract, func, contract_args.as_slice())?;
-    Ok(dbg.chain(tx, Some(block)))
+    let tx = helpers::create_tx(client, addr, contract, func, contract_args.as_slice())?;
+    // chained transactions execute in the same block as the transaction before them
+    dbg.chain(tx, None)
 
 }
 
@@ -188,36 +271,198 @@ pub fn finish() {
     unimplemented!()
 }
 
-pub fn step<T: Transport>(dbg: &mut Debugger<T>, direction: Option<&str>, num: Option<&str>) -> Result<(), Error> {
+pub fn step<T: Transport, P: Patch>(dbg: &mut Debugger<T, P>, direction: Option<&str>, num: Option<&str>) -> Result<(), Error> {
     let num = num.unwrap_or("1").parse().map_err(|_| ShellError::Custom(format!("`{}` is not valid. Must be a positive integer from 0 to 2^32", num.unwrap())))?;
     let direction = direction.unwrap_or("forward");
     debug!("Stepping {} lines", num);
     match direction.parse()? {
-        Direction::Forward => {
-            for _ in 0..=num {
-                dbg.step_forward()?;
+        Direction::Forward | Direction::Into => {
+            for _ in 0..num {
+                dbg.step_into()?;
+            }
+        },
+        Direction::Over => {
+            for _ in 0..num {
+                dbg.step_over()?;
+            }
+        },
+        Direction::Out => {
+            for _ in 0..num {
+                dbg.step_out()?;
             }
         },
         Direction::Backward => {
-            return Err(ShellError::Custom(format!("{}", "Stepping backward not yet stable")).into());
+            for _ in 0..num {
+                dbg.step_backward()?;
+            }
         }
     }
     Ok(())
 }
 
-/// set breakpoints
-pub fn br<T>(dbg: &mut Debugger<T>, line: Option<&str>) -> Result<(), Error> where T: Transport {
-    if let Some(bp) = line {
-        let bp = bp.parse()?;
-        dbg.set_breakpoint(bp)?;
-        Ok(())
+/// set breakpoints: `break [FILE:]LINE [if CONDITION]`, `break *PC [if CONDITION]` or
+/// `break OPCODE [if CONDITION]`
+pub fn br<'a, T, P>(dbg: &mut Debugger<T, P>, mut args: impl Iterator<Item = &'a str>) -> Result<(), Error> where T: Transport, P: Patch {
+    let location = args.next().ok_or_else(|| ShellError::ArgumentsRequired(1, "break".to_string()))?;
+    let condition = match args.next() {
+        Some("if") => Some(args.collect::<Vec<&str>>().join(" ")),
+        Some(other) => return Err(ShellError::Custom(format!("Expected `if CONDITION`, found `{}`", other)).into()),
+        None => None
+    };
+    let condition = condition.as_ref().map(|c| c.as_str());
+    let id = if location.starts_with('*') {
+        dbg.set_pc_breakpoint(number(&location[1..])?, condition)?
+    } else if location.contains(':') || location.chars().all(|c| c.is_ascii_digit()) {
+        let (file, line) = match location.rfind(':') {
+            Some(idx) => (Some(&location[..idx]), &location[idx + 1..]),
+            None => (None, location)
+        };
+        let line = line.parse().map_err(|_| ShellError::Custom(format!("`{}` is not a line number", line)))?;
+        dbg.set_breakpoint(file, line, condition)?
     } else {
-        return Err(ShellError::ArgumentsRequired(1, "break".to_string()).into());
+        dbg.set_opcode_breakpoint(location, condition)?
+    };
+    if let Some(bp) = dbg.breakpoints().into_iter().find(|b| b.id == id) {
+        match bp.contracts.is_empty() {
+            true => println!("\nBreakpoint {} at {}", id, bp.target),
+            false => println!("\nBreakpoint {} at {} in {}", id, bp.target, bp.contracts.join(", ")),
+        }
+    }
+    Ok(())
+}
+
+/// set watchpoints: `watch storage SLOT|EXPR [if CONDITION]` or
+/// `watch memory OFFSET LEN [if CONDITION]`
+pub fn watch<'a, T, P>(dbg: &mut Debugger<T, P>, mut args: impl Iterator<Item = &'a str>) -> Result<(), Error> where T: Transport, P: Patch {
+    let kind = args.next().ok_or_else(|| ShellError::ArgumentsRequired(2, "watch".to_string()))?;
+    let args = args.collect::<Vec<&str>>();
+    // everything after `if` is the condition
+    let (watched, condition) = match args.iter().position(|a| *a == "if") {
+        Some(idx) => (&args[..idx], Some(args[idx + 1..].join(" "))),
+        None => (&args[..], None)
+    };
+    let condition = condition.as_ref().map(|c| c.as_str());
+    let id = match kind {
+        "storage" | "s" => {
+            if watched.is_empty() {
+                return Err(ShellError::ArgumentsRequired(2, "watch".to_string()).into());
+            }
+            dbg.watch_storage(&watched.join(" "), condition)?
+        },
+        "memory" | "m" => {
+            let (offset, len) = match watched {
+                [offset, len] => (number(offset)?, number(len)?),
+                _ => return Err(ShellError::ArgumentsRequired(3, "watch".to_string()).into())
+            };
+            dbg.watch_memory(offset, len, condition)?
+        },
+        other => return Err(ShellError::Custom(format!("Cannot watch `{}`. Watch `storage` or `memory`", other)).into())
+    };
+    if let Some(bp) = dbg.breakpoints().into_iter().find(|b| b.id == id) {
+        println!("\nWatchpoint {}: {}", id, bp.target);
+    }
+    Ok(())
+}
+
+// a decimal or `0x` prefixed hexadecimal number
+fn number(num: &str) -> Result<usize, Error> {
+    let parsed = match num.starts_with("0x") {
+        true => usize::from_str_radix(&num[2..], 16),
+        false => num.parse()
+    };
+    Ok(parsed.map_err(|_| ShellError::Custom(format!("`{}` is not a number", num)))?)
+}
+
+// a word, in decimal or `0x` prefixed hexadecimal
+fn word(num: &str) -> Result<ethereum_types::U256, Error> {
+    let parsed = match num.starts_with("0x") {
+        true => ethereum_types::U256::from_str(&num[2..]).ok(),
+        false => ethereum_types::U256::from_dec_str(num).ok()
+    };
+    Ok(parsed.ok_or_else(|| ShellError::Custom(format!("`{}` is not a number", num)))?)
+}
+
+/// print why execution stopped: the writes that stopped it at watchpoints, or a failure
+pub fn stopped<T, P>(dbg: &Debugger<T, P>) where T: Transport, P: Patch {
+    if let Some(failure) = dbg.failure() {
+        println!("\n{}", failure);
+    }
+    for hit in dbg.watch_hits().iter() {
+        match &hit.line {
+            Some((file, line)) => println!("\nWatchpoint {} at {}:{}", hit.id, file, line),
+            None => println!("\nWatchpoint {}", hit.id),
+        }
+        println!("{}", hit.write);
+    }
+}
+
+/// step a single instruction: `stepi [COUNT]` enters calls, `nexti [COUNT]` runs them without stopping
+pub fn step_instruction<T, P>(dbg: &mut Debugger<T, P>, command: Command, num: Option<&str>) -> Result<(), Error> where T: Transport, P: Patch {
+    let num: usize = num.unwrap_or("1").parse().map_err(|_| ShellError::Custom(format!("`{}` is not a count", num.unwrap())))?;
+    for _ in 0..num {
+        match command {
+            Command::StepInstruction => dbg.step_opcode()?,
+            _ => dbg.next_opcode()?,
+        }
+    }
+    opcode(dbg, Some("0"))
+}
+
+/// `delete`, `enable` or `disable` a breakpoint by its id
+pub fn toggle_breakpoint<T, P>(dbg: &mut Debugger<T, P>, command: Command, id: Option<&str>) -> Result<(), Error> where T: Transport, P: Patch {
+    let id = breakpoint_id(id, &command)?;
+    match command {
+        Command::Delete => dbg.remove_breakpoint(id),
+        Command::Enable => dbg.enable_breakpoint(id, true),
+        _ => dbg.enable_breakpoint(id, false),
+    }
+}
+
+/// pass a breakpoint without stopping: `ignore ID COUNT`
+pub fn ignore<T, P>(dbg: &mut Debugger<T, P>, id: Option<&str>, count: Option<&str>) -> Result<(), Error> where T: Transport, P: Patch {
+    let id = breakpoint_id(id, &Command::Ignore)?;
+    let count = count.ok_or_else(|| ShellError::ArgumentsRequired(2, "ignore".to_string()))?;
+    let count = count.parse().map_err(|_| ShellError::Custom(format!("`{}` is not a count", count)))?;
+    dbg.ignore_breakpoint(id, count)
+}
+
+fn breakpoint_id(id: Option<&str>, command: &Command) -> Result<usize, Error> {
+    let id = id.ok_or_else(|| ShellError::ArgumentsRequired(1, String::from(command)))?;
+    Ok(id.parse().map_err(|_| ShellError::Custom(format!("`{}` is not a breakpoint number", id)))?)
+}
+
+/// `info breakpoints` or `info failures`
+pub fn info<T, P>(dbg: &Debugger<T, P>, item: Option<&str>) -> Result<(), Error> where T: Transport, P: Patch {
+    match item {
+        Some("breakpoints") | Some("break") | Some("b") => {
+            let breakpoints = dbg.breakpoints();
+            if breakpoints.is_empty() {
+                println!("\nNo breakpoints");
+                return Ok(());
+            }
+            println!("\n{:<4} {:<8} Location", "Num", "Enabled");
+            for bp in breakpoints.iter() {
+                println!("{}", bp);
+            }
+            Ok(())
+        },
+        Some("failures") | Some("f") => {
+            let failures = dbg.failures();
+            if failures.is_empty() {
+                println!("\nNo frame has failed");
+            }
+            for failure in failures.iter() {
+                println!("\n{}", failure);
+            }
+            Ok(())
+        },
+        _ => Err(ShellError::Custom("Specify what to show information about: `info breakpoints` or `info failures`".to_string()).into())
     }
 }
 
-pub fn next<T>(dbg: &mut Debugger<T>) -> Result<(), Error> where T: Transport {
+pub fn next<T, P>(dbg: &mut Debugger<T, P>) -> Result<(), Error> where T: Transport, P: Patch {
     dbg.next()?;
+    stopped(dbg);
     Ok(())
 }
 
@@ -225,24 +470,39 @@ pub fn execute() {
     unimplemented!();
 }
 
-pub fn print<T>(dbg: &mut Debugger<T>, item: Option<&str>, num: Option<&str>) -> Result<(), Error> where T: Transport {
+pub fn print<'a, T, P>(dbg: &mut Debugger<T, P>, mut args: impl Iterator<Item = &'a str>) -> Result<(), Error> where T: Transport, P: Patch {
+    let item = args.next();
     if item.is_none() {
         println!("\n{}", dbg.current_range()?);
     } else {
-        let num = num.unwrap_or("1").parse().map_err(|_| ShellError::Custom(format!("`{}` is not valid. Must be a positive integer from 0 to 2^32", num.unwrap())))?;
-        match item.expect("scope is conditional; qed").parse()? {
+        let item = item.expect("scope is conditional; qed");
+        // anything that is not a print value is an expression. Variables in scope shadow print values
+        let print = match item.parse::<Print>().ok().filter(|_| !dbg.in_scope(item)) {
+            Some(p) => p,
+            None => {
+                let expr = std::iter::once(item).chain(args).collect::<Vec<&str>>().join(" ");
+                println!("\n{}", dbg.eval(&expr)?);
+                return Ok(());
+            }
+        };
+        let arg = args.next();
+        // only printing lines takes a number
+        let num = || -> Result<usize, Error> {
+            arg.unwrap_or("1").parse().map_err(|_| ShellError::Custom(format!("`{}` is not valid. Must be a positive integer from 0 to 2^32", arg.unwrap())).into())
+        };
+        match print {
             Print::Current => {
                 let (line, stri) = dbg.current_line()?;
                 println!("\n{}: {}", line, stri);
             },
             Print::Forward => {
-                let lines = dbg.next_lines(num)?;
+                let lines = dbg.next_lines(num()?)?;
                 for (nu, line) in lines.iter() {
                     println!("\n{}: {}", nu, line);
                 }
             },
             Print::Backward => {
-                let lines = dbg.last_lines(num)?;
+                let lines = dbg.last_lines(num()?)?;
                 for (nu, line) in lines.iter() {
                     println!("\n{}: {}", nu, line);
                 }
@@ -253,6 +513,23 @@ pub fn print<T>(dbg: &mut Debugger<T>, item: Option<&str>, num: Option<&str>) ->
                     println!("\nitem {}: {:#x}, b10: {};", i, x, x);
                 })
             },
+            Print::Locals => {
+                let locals = dbg.locals()?;
+                if locals.is_empty() {
+                    println!("\nNo local variables in scope");
+                }
+                for (name, value) in locals.iter() {
+                    println!("\n{}: {}", name, value);
+                }
+            },
+            Print::Var => {
+                let name = arg.ok_or_else(|| ShellError::Custom("Specify the name of a state variable to print".to_string()))?;
+                println!("\n{}: {}", name, dbg.state_variable(name)?);
+            },
+            Print::Expr => {
+                let expr = arg.into_iter().chain(args).collect::<Vec<&str>>().join(" ");
+                println!("\n{}", dbg.eval(&expr)?);
+            },
             Print::Memory => {
                 let mem = dbg.memory()?;
                 mem.iter().enumerate().for_each(|(i, x)| {
@@ -272,6 +549,15 @@ pub fn print<T>(dbg: &mut Debugger<T>, item: Option<&str>, num: Option<&str>) ->
                     });
                 }
             },
+            Print::Logs => {
+                let logs = dbg.logs();
+                if logs.is_empty() {
+                    println!("\nNo events have been emitted");
+                }
+                for (i, log) in logs.iter().enumerate() {
+                    println!("\n#{} {}", i, log);
+                }
+            },
             Print::Result => {
                 println!("\n");
                 dbg.output()
@@ -286,6 +572,144 @@ pub fn print<T>(dbg: &mut Debugger<T>, item: Option<&str>, num: Option<&str>) ->
     }
     Ok(())
 }
+/// print the call stack
+pub fn backtrace<T, P>(dbg: &Debugger<T, P>) -> Result<(), Error> where T: Transport, P: Patch {
+    for (i, frame) in dbg.backtrace()?.iter().enumerate() {
+        match (&frame.contract, &frame.file, frame.line) {
+            (Some(contract), Some(file), Some(line)) => {
+                let function = frame.function.as_ref().map(|f| f.as_str()).unwrap_or("??");
+                println!("\n#{} {}::{} at {}:{}", i, contract, function, file, line);
+            },
+            _ => println!("\n#{} ?? at {:#x}", i, frame.address),
+        }
+    }
+    Ok(())
+}
+
+/// run the rest of the transaction, printing the lines and functions that used the most gas.
+/// The whole profile is written to `path` as JSON
+pub fn profile<T, P>(dbg: &mut Debugger<T, P>, path: Option<&str>) -> Result<(), Error> where T: Transport, P: Patch {
+    let profile = dbg.profile()?;
+    println!("\nGas used by instructions: {}", profile.gas);
+    println!("\n{:>10} {:>8}  Line", "Gas", "Instrs");
+    for line in profile.lines.iter().take(PROFILE_ROWS) {
+        println!("{}", line);
+    }
+    if !profile.functions.is_empty() {
+        println!("\n{:>10} {:>8}  Function", "Gas", "Instrs");
+        for function in profile.functions.iter().take(PROFILE_ROWS) {
+            println!("{}", function);
+        }
+    }
+    if let Some(path) = path {
+        std::fs::write(path, profile.to_json()?)?;
+        println!("\nProfile written to {}", path);
+    }
+    Ok(())
+}
+
+/// start or stop recording coverage, or write the coverage recorded to a file in LCOV format
+pub fn coverage<T, P>(dbg: &mut Debugger<T, P>, arg: Option<&str>) -> Result<(), Error> where T: Transport, P: Patch {
+    match arg {
+        Some("on") => {
+            if dbg.coverage().is_none() {
+                dbg.record_coverage(Coverage::default());
+            }
+            println!("\nRecording coverage");
+        },
+        Some("off") => {
+            dbg.take_coverage();
+            println!("\nStopped recording coverage");
+        },
+        Some(path) => {
+            let lcov = dbg.lcov().ok_or_else(|| ShellError::Custom("Coverage is not being recorded. Start recording with `coverage on`".to_string()))?;
+            std::fs::write(path, lcov)?;
+            println!("\nCoverage written to {}", path);
+        },
+        None => return Err(ShellError::Custom("Specify `coverage on`, `coverage off` or a file to write coverage to".to_string()).into())
+    }
+    Ok(())
+}
+
+/// run the rest of the transaction, writing a trace of every instruction to `path`
+pub fn trace<T, P>(dbg: &mut Debugger<T, P>, path: Option<&str>) -> Result<(), Error> where T: Transport, P: Patch {
+    let path = path.ok_or_else(|| ShellError::Custom("Specify a file to write the trace to".to_string()))?;
+    let trace = dbg.trace()?;
+    std::fs::write(path, trace.to_json()?)?;
+    println!("\nTraced {} instructions to {}", trace.struct_logs.len(), path);
+    Ok(())
+}
+
+/// override the state of an account, in the session that is open if it has not started and in
+/// every session opened after: `override balance|nonce ADDRESS VALUE`,
+/// `override storage ADDRESS SLOT VALUE` or `override code ADDRESS FILE`
+pub fn override_state<'a, T, P>(dbg: Option<&mut Debugger<T, P>>,
+                                overrides: &mut HashMap<Address, StateOverride>,
+                                mut args: impl Iterator<Item = &'a str>)
+-> Result<(), Error> where T: Transport, P: Patch
+{
+    let (kind, address) = match (args.next(), args.next()) {
+        (Some(kind), Some(address)) => (kind, address),
+        _ => return Err(ShellError::ArgumentsRequired(3, "override".to_string()).into())
+    };
+    let address = Address::from_str(address.trim_start_matches("0x")).map_err(|_| ShellError::Custom(format!("Invalid address `{}`", address)))?;
+    let mut value = || args.next().ok_or_else(|| ShellError::ArgumentsRequired(3, "override".to_string()));
+    let state = match kind {
+        "balance" => StateOverride { balance: Some(bigint::U256(word(value()?)?.0)), ..StateOverride::default() },
+        "nonce" => StateOverride { nonce: Some(bigint::U256(word(value()?)?.0)), ..StateOverride::default() },
+        "storage" => {
+            let slot = bigint::U256(word(value()?)?.0);
+            let contents = bigint::M256(bigint::U256(word(value()?)?.0));
+            StateOverride::default().with_storage(slot, contents)
+        },
+        "code" => {
+            let path = value()?;
+            let code = std::fs::read_to_string(path)?;
+            let code = hex::decode(code.trim().trim_start_matches("0x"))
+                .map_err(|_| ShellError::Custom(format!("`{}` does not hold hex encoded code", path)))?;
+            StateOverride { code: Some(code), ..StateOverride::default() }
+        },
+        other => return Err(ShellError::Custom(format!("Cannot override `{}`. Override `balance`, `nonce`, `storage` or `code`", other)).into())
+    };
+    match dbg {
+        Some(dbg) if !dbg.started() => {
+            dbg.override_state(address, &state)?;
+            println!("\nOverrode the {} of {:#x}", kind, address);
+        },
+        _ => println!("\nOverrode the {} of {:#x} from the next session. `reset` to start this one again with it", kind, address)
+    }
+    overrides.entry(address).or_insert_with(StateOverride::default).merge(state);
+    Ok(())
+}
+
+/// change the state execution is paused in: `set stack INDEX VALUE`, `set mem OFFSET HEX` or
+/// `set storage SLOT VALUE`
+pub fn set_state<'a, T, P>(dbg: &mut Debugger<T, P>, mut args: impl Iterator<Item = &'a str>) -> Result<(), Error> where T: Transport, P: Patch {
+    let (kind, at, value) = match (args.next(), args.next(), args.next()) {
+        (Some(kind), Some(at), Some(value)) => (kind, at, value),
+        _ => return Err(ShellError::ArgumentsRequired(3, "set".to_string()).into())
+    };
+    match kind {
+        "stack" => {
+            let value = word(value)?;
+            dbg.set_stack(number(at)?, value)?;
+            println!("\nStack item {} is now {:#x}", at, value);
+        },
+        "mem" | "memory" => {
+            let bytes = hex::decode(value.trim_start_matches("0x")).map_err(|_| ShellError::Custom(format!("`{}` is not hex", value)))?;
+            dbg.set_memory(number(at)?, bytes.as_slice())?;
+            println!("\nWrote {} bytes of memory at {}", bytes.len(), at);
+        },
+        "storage" => {
+            let (slot, value) = (word(at)?, word(value)?);
+            dbg.set_storage(slot, value)?;
+            println!("\nStorage slot {:#x} is now {:#x}", slot, value);
+        },
+        other => return Err(ShellError::Custom(format!("Cannot set `{}`. Set `stack`, `mem` or `storage`", other)).into())
+    }
+    Ok(())
+}
+
 /*
 pub fn stack() {
     unimplemented!();
@@ -299,13 +723,27 @@ pub fn storage() {
     unimplemented!();
 }
 */
-pub fn opcode() {
-    unimplemented!();
+/// disassemble the code around the next instruction: `opcode [COUNT]`
+pub fn opcode<T, P>(dbg: &Debugger<T, P>, count: Option<&str>) -> Result<(), Error> where T: Transport, P: Patch {
+    let count = count.unwrap_or("5").parse().map_err(|_| ShellError::Custom(format!("`{}` is not a count", count.unwrap())))?;
+    println!();
+    for dis in dbg.disassemble(count, count)?.iter() {
+        let marker = if dis.current { "=>" } else { "  " };
+        let instruction = format!("{} {:>6x}  {}", marker, dis.instruction.offset, dis.instruction);
+        match (&dis.file, dis.line, &dis.source) {
+            (Some(file), Some(line), Some(source)) => {
+                // only the first line of the source, so that every instruction takes one line
+                let source: String = source.lines().next().unwrap_or("").trim().chars().take(40).collect();
+                println!("{:<40} // {}:{} {}", instruction, file, line, source);
+            },
+            _ => println!("{}", instruction),
+        }
+    }
+    Ok(())
 }
 
 pub fn quit() {
     println!("\nGoodbye!");
-    std::process::exit(0);
 }
 
 
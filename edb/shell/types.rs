@@ -28,12 +28,28 @@ pub const HELP: &'static str = "
     Available Commands:
     help - Display this message
     clear - clear the terminal
-    set - Set the parameters for the function that will be debugged
+    set - Set the parameters for the function that will be debugged. While paused, `set stack|mem|storage` changes the state execution continues from
+    create - Set the parameters for a contract constructor that will be debugged
+    debug-tx - Debug a transaction that has already been mined
     run - Run a contract/function to debug
     reset - Reset to the first breakpoint
     chain - Chain the previous transaction into another, preserving the state trie
-    step - Step a line of execution
+    step - Step a line of execution. `step in`, `step over` and `step out` control how function calls are stepped
+    stepi - Step a single EVM instruction. `nexti` steps over calls into other contracts
     next - Go to the next breakpoint
-    break - Set a breakpoint
+    break - Set a breakpoint, optionally with a condition: `break [FILE:]LINE [if CONDITION]`. `break *PC` and `break OPCODE` stop at instructions
+    watch - Stop when storage or memory is written: `watch storage SLOT|EXPR`, `watch memory OFFSET LEN`
+    delete - Delete a breakpoint
+    enable - Enable a breakpoint
+    disable - Disable a breakpoint
+    ignore - Pass a breakpoint a number of times without stopping
+    info - Show information, ie `info breakpoints` or `info failures`
+    print - Print source lines, the stack, memory, storage, variables, event logs or the value of an expression
+    backtrace - Print the call stack. Alias: `bt`
+    profile - Run the rest of the transaction, showing the gas used by each line and function: `profile [FILE]`
+    coverage - Record which lines are executed: `coverage on`, and write them to a file in LCOV format: `coverage FILE`
+    override - Run against modified state: `override storage ADDRESS SLOT VALUE`, `override code ADDRESS FILE`, or the `balance` or `nonce` of an account
+    trace - Run the rest of the transaction, writing a geth-style trace of every instruction to a file: `trace FILE`
+    opcode - Disassemble the code around the next instruction. Alias: `disas`
     quit - use `quit` or `exit` to escape the shell
 ";
@@ -0,0 +1,128 @@
+//! A cache of account state read from nodes, kept on disk between sessions
+use std::{rc::Rc, path::PathBuf, collections::BTreeMap};
+use futures::future::{self, Future};
+use jsonrpc_core::{Call, Params, Value};
+use log::*;
+use web3::{Transport, RequestId, types::{BlockId, BlockNumber}};
+use super::err::EmulError;
+use super::node;
+use super::simpledb::{SimpleDB, Batched};
+
+/// A transport that keeps the state of accounts read through it in a file, and answers from the
+/// file when the same state is read again, in this session or a later one.
+/// State is keyed by chain, block number, address and slot of storage. The balance, nonce and
+/// code of an account take the slots `balance`, `nonce` and `code`. State read at the latest or
+/// pending block may still change, so it is never cached.
+/// Entries are written in batches, and the rest once the cache and all its clones are dropped
+#[derive(Debug, Clone)]
+pub struct StateCache<T: Transport> {
+    inner: T,
+    chain: String,
+    entries: Rc<Batched<BTreeMap<String, Value>>>,
+}
+
+impl<T: Transport> StateCache<T> {
+    /// Cache the state `inner` reads in `path`, adding to what is cached there already
+    pub fn new(inner: T, path: PathBuf) -> Result<Self, EmulError> {
+        let chain = chain(&inner)?;
+        let entries: Batched<BTreeMap<String, Value>> = Batched::open(SimpleDB::new(path)?)?;
+        info!("{} entries of state cached", entries.data().len());
+        Ok(StateCache { inner, chain, entries: Rc::new(entries) })
+    }
+
+    /// Number of words of storage, balances, nonces and codes cached, for every chain and block
+    pub fn len(&self) -> usize {
+        self.entries.data().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.data().is_empty()
+    }
+
+    // `chain:block:address:slot` if the request reads state that cannot change
+    fn key(&self, call: &Call) -> Option<String> {
+        let (method, params) = match call {
+            Call::MethodCall(call) => match &call.params {
+                Some(Params::Array(params)) => (call.method.as_str(), params),
+                _ => return None
+            },
+            _ => return None
+        };
+        let address = params.first()?.as_str()?.to_lowercase();
+        let block = match params.last()?.as_str()? {
+            "earliest" => 0,
+            n if n.starts_with("0x") => u64::from_str_radix(&n[2..], 16).ok()?,
+            _ => return None
+        };
+        let slot = match method {
+            "eth_getBalance" => "balance".to_string(),
+            "eth_getTransactionCount" => "nonce".to_string(),
+            "eth_getCode" => "code".to_string(),
+            "eth_getStorageAt" if params.len() == 3 => params[1].as_str()?.to_lowercase(),
+            _ => return None
+        };
+        Some(format!("{}:{}:{}:{}", self.chain, block, address, slot))
+    }
+}
+
+// the chain id of the chain `inner` reads, or the hash of its genesis block if the node does not
+// know `eth_chainId`. The network id cannot tell apart chains that share it
+fn chain<T: Transport>(inner: &T) -> Result<String, EmulError> {
+    if let Ok(Value::String(id)) = inner.execute("eth_chainId", Vec::new()).wait() {
+        return Ok(id);
+    }
+    let genesis = node::block(&web3::Web3::new(inner.clone()), BlockId::Number(BlockNumber::Earliest))?;
+    let hash = genesis.hash.ok_or_else(|| EmulError::BlockNotFound("Genesis".to_string()))?;
+    Ok(format!("{:#x}", hash))
+}
+
+impl<T> Transport for StateCache<T> where T: Transport, T::Out: 'static {
+    type Out = Box<dyn Future<Item = Value, Error = web3::Error>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        self.inner.prepare(method, params)
+    }
+
+    fn send(&self, id: RequestId, request: Call) -> Self::Out {
+        let key = match self.key(&request) {
+            Some(key) => key,
+            None => return Box::new(self.inner.send(id, request))
+        };
+        if let Some(value) = self.entries.data().get(&key) {
+            trace!("Found {} in the state cache", key);
+            return Box::new(future::ok(value.clone()));
+        }
+        let entries = self.entries.clone();
+        Box::new(self.inner.send(id, request).map(move |value| {
+            entries.change(|entries| entries.insert(key, value.clone()).is_none());
+            value
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculate::speculate;
+    use edb_test_helpers as edbtest;
+    use web3::types::{BlockNumber, H160};
+
+    speculate! {
+        it "keeps state read at a block between sessions" {
+            let path = std::env::temp_dir().join("edb-state-cache-test");
+            let _ = std::fs::remove_file(&path);
+            let cache = StateCache::new(edbtest::MockWeb3Transport::default(), path.clone()).unwrap();
+            let client = web3::Web3::new(cache.clone());
+            let balance = client.eth().balance(H160::zero(), Some(BlockNumber::Number(5))).wait().unwrap();
+            client.eth().balance(H160::zero(), Some(BlockNumber::Latest)).wait().unwrap();
+            assert_eq!(cache.len(), 1);
+            // the entry is saved once the cache is dropped
+            drop((client, cache));
+
+            let cache = StateCache::new(edbtest::MockWeb3Transport::default(), path).unwrap();
+            assert_eq!(cache.len(), 1);
+            let client = web3::Web3::new(cache);
+            assert_eq!(client.eth().balance(H160::zero(), Some(BlockNumber::Number(5))).wait().unwrap(), balance);
+        }
+    }
+}
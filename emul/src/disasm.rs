@@ -0,0 +1,119 @@
+//! Disassembles EVM bytecode into instructions
+use sputnikvm::Opcode;
+use std::fmt;
+
+// Solidity appends CBOR-encoded metadata to runtime bytecode: 0xa1 0x65 "bzzr0" 0x58 0x20
+const METADATA: [u8; 9] = [0xa1, 0x65, 0x62, 0x7a, 0x7a, 0x72, 0x30, 0x58, 0x20];
+
+/// One instruction of bytecode
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    /// index of the instruction, counting every instruction before it
+    pub position: usize,
+    /// byte offset of the instruction in the code
+    pub offset: usize,
+    pub opcode: Opcode,
+    /// data a `PUSH` places on the stack
+    pub data: Vec<u8>,
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", name(self.opcode))?;
+        if !self.data.is_empty() {
+            write!(f, " 0x")?;
+            for byte in self.data.iter() {
+                write!(f, "{:02x}", byte)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Walks bytecode instruction by instruction, skipping over the data of `PUSH` instructions.
+/// Stops at the end of the code, or at the metadata the Solidity compiler appends to it
+#[derive(Debug, Clone)]
+pub struct Instructions<'a> {
+    code: &'a [u8],
+    offset: usize,
+    position: usize,
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Instruction;
+
+    fn next(&mut self) -> Option<Instruction> {
+        if self.offset >= self.code.len() || self.code[self.offset..].starts_with(&METADATA) {
+            return None;
+        }
+        let opcode = Opcode::from(self.code[self.offset]);
+        let size = match opcode {
+            Opcode::PUSH(bytes) => bytes,
+            _ => 0
+        };
+        // a `PUSH` at the end of the code may be cut short
+        let start = (self.offset + 1).min(self.code.len());
+        let end = (start + size).min(self.code.len());
+        let instruction = Instruction {
+            position: self.position,
+            offset: self.offset,
+            opcode,
+            data: self.code[start..end].to_vec(),
+        };
+        self.offset += size + 1;
+        self.position += 1;
+        Some(instruction)
+    }
+}
+
+/// The instructions of `code`, in order
+pub fn instructions(code: &[u8]) -> Instructions<'_> {
+    Instructions { code, offset: 0, position: 0 }
+}
+
+/// Name of an opcode, as it is written in assembly
+pub fn name(opcode: Opcode) -> String {
+    match opcode {
+        Opcode::PUSH(n) => format!("PUSH{}", n),
+        Opcode::DUP(n) => format!("DUP{}", n),
+        Opcode::SWAP(n) => format!("SWAP{}", n),
+        Opcode::LOG(n) => format!("LOG{}", n),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Whether `opcode` is in the class `class`: either the name of one opcode, ie `SSTORE` or
+/// `PUSH1`, or of a family of opcodes, ie `PUSH` or `LOG`. Case is ignored
+pub fn in_class(opcode: Opcode, class: &str) -> bool {
+    let name = name(opcode);
+    let class = class.to_ascii_uppercase();
+    name == class || (name.starts_with(&class) && name[class.len()..].chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Whether any opcode is in the class `class`
+pub fn is_class(class: &str) -> bool {
+    (0..=255u8).any(|byte| in_class(Opcode::from(byte), class))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculate::speculate;
+
+    speculate! {
+        it "disassembles up to the metadata" {
+            // PUSH1 0x80 LOG1 PUSH2 0x0102, then metadata
+            let mut code = vec![0x60, 0x80, 0xa1, 0x61, 0x01, 0x02];
+            code.extend_from_slice(&METADATA);
+            let listing = instructions(&code).map(|i| (i.offset, i.to_string())).collect::<Vec<_>>();
+            assert_eq!(listing, vec![(0, "PUSH1 0x80".to_string()), (2, "LOG1".to_string()), (3, "PUSH2 0x0102".to_string())]);
+        }
+
+        it "matches opcode classes" {
+            assert!(in_class(Opcode::SSTORE, "sstore"));
+            assert!(in_class(Opcode::PUSH(4), "PUSH"));
+            assert!(!in_class(Opcode::CALLCODE, "CALL"));
+            assert!(!is_class("JUMPY"));
+        }
+    }
+}
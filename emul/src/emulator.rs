@@ -1,13 +1,25 @@
 //! Emulates transaction execution and allows for real-time debugging.
 //! debugs one transaction at a time (1:1 One VM, One TX)
-use sputnikvm::{Opcode, VMStatus, ValidTransaction, TransactionAction, HeaderParams, SeqTransactionVM, SeqMemory, AccountChange, errors::{RequireError, CommitError}, AccountCommitment, VM, Storage, PC};
-use sputnikvm_network_foundation::ByzantiumPatch;
-use web3::{ api::Web3, Transport, types::{BlockNumber, U256, Bytes}};
+use sputnikvm::{Opcode, Memory, VMStatus, MachineStatus, ValidTransaction, TransactionAction, HeaderParams, SeqTransactionVM, SeqMemory, AccountChange, errors::{RequireError, CommitError, OnChainError}, AccountCommitment, VM, Storage, PC, Patch, State};
+use web3::{ api::Web3, Transport, types::{BlockId, BlockNumber, H256, U256, Bytes}};
 use futures::future::Future;
 use failure::Error;
 use log::*;
-use std::{ rc::Rc, cell::RefCell, collections::{HashMap} };
-use super::err::{EmulError, StateError};
+use std::{ rc::Rc, cell::RefCell, collections::{HashMap, BTreeMap} };
+use super::err::{EmulError, VmError};
+use super::node;
+use super::frame::{Frame, FrameKind};
+use super::disasm;
+use super::write::Write;
+use super::fault::{Failure, FailureKind};
+use super::event::EventLog;
+use super::trace::{self, StructLog, Trace};
+use super::overrides::StateOverride;
+
+/// How many instructions to step between taking a snapshot of the VM
+const CHECKPOINT_INTERVAL: usize = 1000;
+/// Most bytes of memory read at once, to observe a write or for the data of a revert or log
+const MAX_MEMORY_READ: usize = 1 << 16;
 
 /// An action or what should happen for the next step of execution
 pub enum Action {
@@ -38,32 +50,62 @@ impl Account {
     }
 }
 
+/// A snapshot of the VM and locally cached state, used to step backwards without replaying the
+/// entire transaction
+struct Checkpoint<P: Patch> {
+    /// the number of instructions that had been stepped when the snapshot was taken
+    step: usize,
+    vm: SeqTransactionVM<P>,
+    state_cache: HashMap<bigint::H160, Account>,
+    frames: Vec<Frame>,
+    returns: Vec<Write>,
+}
+
+/// Called with a write an instruction made
+type Observer = Box<dyn FnMut(&Write)>;
+
 /// Emulation Object
-pub struct Emulator<T: Transport> {
-    vm: SeqTransactionVM<ByzantiumPatch>,
+/// `P` is the hard fork whose rules the VM executes with
+pub struct Emulator<T: Transport, P: Patch> {
+    vm: SeqTransactionVM<P>,
+    // the position of every instruction we have stepped
     positions: Vec<usize>,
     transaction: (ValidTransaction, HeaderParams),
     client: web3::Web3<T>,
+    // block that account state is read from
+    state_block: BlockNumber,
     state_cache: Rc<RefCell<HashMap<bigint::H160, Account>>>,
-    // the amount of instructions have we stepped
+    // snapshots, ordered by the step they were taken at. The first is always the un-started VM
+    checkpoints: Vec<Checkpoint<P>>,
+    // one frame for every machine on the call stack, outermost first
+    frames: Vec<Frame>,
+    // memory the return data of calls still executing will be written to, innermost call last
+    returns: Vec<Write>,
+    // called with every write to storage or memory made by an instruction that is stepped
+    observers: Vec<Observer>,
+    // frames that failed while stepping, with the number of instructions stepped once they had
+    failures: Vec<(usize, Failure)>,
+    // for every frame that failed while stepping, the steps it was executing during and its depth:
+    // (instructions stepped before it was entered, instructions stepped once it had failed, depth)
+    reverts: Vec<(usize, usize, usize)>,
+    // event logs emitted while stepping, with the number of instructions stepped once they had
+    logs: Vec<(usize, EventLog)>,
+    // the state before every instruction stepped, with the number of instructions stepped once
+    // they had. `None` unless tracing
+    struct_logs: Option<Vec<(usize, StructLog)>>,
+    // storage slots of each account the traced instructions have read or written
+    traced_storage: HashMap<bigint::H160, BTreeMap<String, String>>,
 }
 
 /// A vm that emulates a transaction, allowing for mutations during execution
-impl<T> Emulator<T> where T: Transport {
-    /// Create a new Emulator
+impl<T, P> Emulator<T, P> where T: Transport, P: Patch {
+    /// Create a new Emulator, executing against the state of block `state`.
+    /// All account state is read from this block, and header parameters are derived from it.
     ///
     /// example assumes the deployment of `SimpleStorage` contract to `0x884531EaB1bA4a81E9445c2d7B64E29c2F14587C`
     ///
     /// ```rust,no_run
     ///  let web3 = web3::Web3::new(http);
-    ///  let block = web3.eth().block(BlockId::Number(BlockNumber::Latest)).wait().unwrap();
-    ///  let headers = sputnikvm::HeaderParams {
-    ///     beneficiary: block.author,
-    ///     timestamp: block.timestamp,
-    ///     number: block.number.unwrap().to_u256(),
-    ///     difficulty: block.difficulty,
-    ///     gas_limit: block.gas_limit
-    /// };
     /// let set = contract.function("set").unwrap().encode_input(&[ethabi::Token::Uint(U256::from("1337"))]).unwrap();
     ///
     /// let tx_set = ValidTransaction {
@@ -75,16 +117,71 @@ impl<T> Emulator<T> where T: Transport {
     ///     input: Rc::new(set),
     ///     nonce: bigint::U256::zero(),
     /// };
-    /// let emul = Emulator::new(tx_set, headers, web3);
+    /// let emul = Emulator::<_, ByzantiumPatch>::new(tx_set, BlockId::Number(BlockNumber::Number(6285997)), web3)?;
     /// ```
-    pub fn new(transaction: ValidTransaction, block: HeaderParams, client: Web3<T>) -> Self {
-        Emulator {
+    pub fn new(transaction: ValidTransaction, state: BlockId, client: Web3<T>) -> Result<Self, EmulError> {
+        let block = node::block(&client, state)?;
+        let header = node::header_params(&block)?;
+        let number = node::number(&block)?;
+        Ok(Self::with_header(transaction, header, number, client))
+    }
+
+    /// Create a new Emulator with explicit header parameters, reading account state from block `state`
+    pub fn with_header(transaction: ValidTransaction, block: HeaderParams, state: BlockNumber, client: Web3<T>) -> Self {
+        let mut emul = Emulator {
             transaction: (transaction.clone(), block.clone()),
             vm: sputnikvm::TransactionVM::new(transaction, block),
             positions: Vec::new(),
             client,
+            state_block: state,
             state_cache: Rc::new(RefCell::new(HashMap::new())),
+            checkpoints: Vec::new(),
+            frames: Vec::new(),
+            returns: Vec::new(),
+            observers: Vec::new(),
+            failures: Vec::new(),
+            reverts: Vec::new(),
+            logs: Vec::new(),
+            struct_logs: None,
+            traced_storage: HashMap::new(),
+        };
+        emul.checkpoint();
+        emul
+    }
+
+    /// Create an Emulator that replays a transaction which has already been mined.
+    /// Account state is read from the block preceding the one the transaction was included in,
+    /// and every transaction that came before it in its block is executed first, so that the
+    /// transaction sees exactly the state it saw on chain.
+    ///
+    /// ```rust,no_run
+    /// let hash = H256::from_str("9ee8e1e8b0d9fb8a4b7b2e6a1dfd1a0c0b2f1ac7c7e2b0b4a5f0e6a2b5d9ea11").unwrap();
+    /// let emul = Emulator::<_, ByzantiumPatch>::replay(hash, web3)?;
+    /// ```
+    pub fn replay(hash: H256, client: Web3<T>) -> Result<Self, EmulError> {
+        let tx = node::transaction(&client, hash)?;
+        let block_hash = tx.block_hash.ok_or(EmulError::TransactionPending(hash))?;
+        let block = node::block_with_txs(&client, BlockId::Hash(block_hash))?;
+        let header = node::header_params(&block)?;
+        let state = match node::number(&block)? {
+            BlockNumber::Number(0) => BlockNumber::Earliest,
+            BlockNumber::Number(n) => BlockNumber::Number(n - 1),
+            _ => unreachable!("node::number only returns mined block numbers; qed")
+        };
+
+        let mut emul = Self::with_header(node::valid_transaction(&tx), header, state, client);
+        for prior in block.transactions.iter().take_while(|prior| prior.hash != hash) {
+            debug!("Executing transaction {:#x} preceding {:#x}", prior.hash, hash);
+            emul.chain(node::valid_transaction(prior), None)?;
+            emul.run()?;
         }
+        emul.chain(node::valid_transaction(&tx), None)?;
+        Ok(emul)
+    }
+
+    /// The block that account state is read from
+    pub fn state_block(&self) -> BlockNumber {
+        self.state_block
     }
 
     /// fire the vm, with the specified Action
@@ -102,24 +199,249 @@ impl<T> Emulator<T> where T: Transport {
         }
     }
 
-    pub fn memory(&self) -> Result<&SeqMemory<ByzantiumPatch>, EmulError> {
+    pub fn memory(&self) -> Result<&SeqMemory<P>, EmulError> {
         Ok(&self.vm.current_state().ok_or(EmulError::CouldNotAcquireVm)?.memory)
     }
 
+    /// storage of the account the current frame executes against
     pub fn storage(&self) -> Option<HashMap<bigint::U256, bigint::M256>> {
+        let address = self.frame().map(|f| f.address).unwrap_or_else(|| self.resident_address());
         self.state_cache.borrow()
-            .get(&self.resident_address())
+            .get(&address)
             .map(|acc| acc.storage.clone())
     }
 
-    // The address of the contract that is being debugged
-    fn resident_address(&self) -> bigint::H160 {
+    /// Read a word of an account's storage.
+    /// Slots the VM has not touched yet are fetched from the node at the state block
+    pub fn storage_at(&self, address: bigint::H160, index: bigint::U256) -> Result<bigint::M256, EmulError> {
+        let cached = self.state_cache.borrow().get(&address).and_then(|acc| acc.storage.get(&index).cloned());
+        if let Some(value) = cached {
+            return Ok(value);
+        }
+        let value = self.client.eth().storage(ethereum_types::H160(address.0), ethereum_types::U256(index.0), Some(self.state_block)).wait()?;
+        Ok(bigint::M256(bigint::U256(unsafe { super::scary::non_scalar_typecast::h256_to_u256(value) })))
+    }
+
+    /// Read a word of an account's storage as the executing frame sees it, including what it and
+    /// the frames that called it have written. Slots the VM has not read yet are read like
+    /// `storage_at`
+    pub fn frame_storage_at(&self, address: bigint::H160, index: bigint::U256) -> Result<bigint::M256, EmulError> {
+        if let Some(state) = self.vm.current_state() {
+            match state.account_state.storage_read(address, index) {
+                Ok(value) => return Ok(value),
+                Err(RequireError::Account(_)) | Err(RequireError::AccountStorage(_, _)) => (),
+                Err(e) => return Err(EmulError::Vm(VmError::Require(e)))
+            }
+        }
+        self.storage_at(address, index)
+    }
+
+    /// Execute against `state` instead of the state of `address` the node holds.
+    /// Must be called before the transaction starts
+    pub fn override_state(&mut self, address: bigint::H160, state: &StateOverride) -> Result<(), EmulError> {
+        if self.started() {
+            return Err(EmulError::AlreadyStarted);
+        }
+        if !self.state_cache.borrow().contains_key(&address) {
+            let account = fetch_account(address, &self.client, &self.state_block)?;
+            self.state_cache.borrow_mut().insert(address, account);
+        }
+        {
+            let mut cache = self.state_cache.borrow_mut();
+            let account = cache.get_mut(&address).expect("inserted if missing; qed");
+            if let Some(balance) = state.balance {
+                account.balance = balance;
+            }
+            if let Some(nonce) = state.nonce {
+                account.nonce = nonce;
+            }
+            if let Some(code) = &state.code {
+                account.code = Rc::new(code.clone());
+            }
+            account.storage.extend(state.storage.iter().map(|(k, v)| (*k, *v)));
+        }
+        // the VM reads the accounts it requires from the cache first, including after stepping
+        // back to the first checkpoint
+        self.checkpoints.clear();
+        self.checkpoint();
+        Ok(())
+    }
+
+    /// Replace item `index` of the stack of the executing frame, counting from the top, with `value`
+    pub fn set_stack(&mut self, index: usize, value: bigint::M256) -> Result<(), EmulError> {
+        self.mutate(|state| {
+            state.stack.set(index, value).map_err(|_| EmulError::Mutation(format!("the stack has {} items", state.stack.len())))
+        })
+    }
+
+    /// Overwrite the memory of the executing frame from `offset` with `bytes`.
+    /// Memory cannot grow this way, since growing it costs gas
+    pub fn set_memory(&mut self, offset: usize, bytes: &[u8]) -> Result<(), EmulError> {
+        self.mutate(|state| {
+            match offset.checked_add(bytes.len()) {
+                Some(end) if end <= state.memory.len() => (),
+                _ => return Err(EmulError::Mutation(format!("memory is {} bytes", state.memory.len())))
+            }
+            for (i, byte) in bytes.iter().enumerate() {
+                state.memory.write_raw((offset + i).into(), *byte).map_err(|e| EmulError::Mutation(format!("{:?}", e)))?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Write `value` to slot `index` of the storage of the account the executing frame executes against
+    pub fn set_storage(&mut self, index: bigint::U256, value: bigint::M256) -> Result<(), EmulError> {
+        // the slot is read from the node first, as `SSTORE` would
+        self.require_storage(index)?;
+        self.mutate(|state| {
+            let address = state.context.address;
+            state.account_state.storage_write(address, index, value).map_err(|e| EmulError::Mutation(format!("{:?}", e)))
+        })
+    }
+
+    // make sure slot `index` of the account the executing frame executes against has been read
+    fn require_storage(&mut self, index: bigint::U256) -> Result<(), EmulError> {
+        'require: loop {
+            let read = {
+                let state = self.vm.current_state().ok_or(EmulError::CouldNotAcquireVm)?;
+                state.account_state.storage_read(state.context.address, index).map(|_| ())
+            };
+            if handle_requires(&read, self.state_cache.clone(), &mut self.vm, &self.client, &self.state_block)? {
+                break 'require;
+            }
+        }
+        Ok(())
+    }
+
+    // read the slot the next instruction writes, if it is an `SSTORE`, so the value it replaces is known
+    fn require_pending_storage(&mut self) -> Result<(), EmulError> {
+        if self.executing() && self.opcode().ok() == Some(Opcode::SSTORE) {
+            if let Some(slot) = self.vm.current_state().and_then(|s| s.stack.peek(0).ok()) {
+                self.require_storage(slot.0)?;
+            }
+        }
+        Ok(())
+    }
+
+    // change the state of the machine execution is paused in
+    fn mutate<F>(&mut self, fun: F) -> Result<(), EmulError> where F: FnOnce(&mut State<SeqMemory<P>, P>) -> Result<(), EmulError> {
+        if !self.executing() {
+            return Err(EmulError::NotPaused);
+        }
+        fun(self.vm.current_state_mut().ok_or(EmulError::CouldNotAcquireVm)?)?;
+        // stepping back to here, or replaying past here, must start from the changed state
+        if self.checkpoints.last().map(|c| c.step == self.positions.len()).unwrap_or(false) {
+            self.checkpoints.pop();
+        }
+        self.checkpoint();
+        Ok(())
+    }
+
+    /// The call frames of the transaction, outermost first. Empty if the VM has not been started
+    pub fn frames(&self) -> &[Frame] {
+        self.frames.as_slice()
+    }
+
+    /// The frame that is currently executing
+    pub fn frame(&self) -> Option<&Frame> {
+        self.frames.last()
+    }
+
+    /// The address of the contract that is being debugged.
+    /// If the transaction creates a contract, this is the address the contract will be created at
+    pub fn resident_address(&self) -> bigint::H160 {
         let (tx, _) = &self.transaction;
         match tx.action {
             TransactionAction::Call(addr) => addr,
-            _ => panic!("Cannot debug creation of contracts")
+            TransactionAction::Create => tx.address(),
+        }
+    }
+
+    /// Whether the transaction being emulated creates a contract
+    pub fn is_creation(&self) -> bool {
+        match self.transaction.0.action {
+            TransactionAction::Create => true,
+            _ => false
         }
     }
+
+    /// The transaction being emulated
+    pub fn transaction(&self) -> &ValidTransaction {
+        &self.transaction.0
+    }
+
+    /// The header of the block the transaction is emulated in
+    pub fn header(&self) -> &HeaderParams {
+        &self.transaction.1
+    }
+
+    /// The account that called the current frame, ie `msg.sender`
+    pub fn caller(&self) -> bigint::H160 {
+        self.vm.current_state()
+            .map(|state| state.context.caller)
+            .unwrap_or_else(|| self.transaction.0.caller.unwrap_or_default())
+    }
+
+    /// Wei sent along with the call into the current frame, ie `msg.value`
+    pub fn call_value(&self) -> bigint::U256 {
+        self.vm.current_state().map(|state| state.context.value).unwrap_or(self.transaction.0.value)
+    }
+
+    /// Input data of the current frame, ie `msg.data`
+    pub fn call_data(&self) -> Rc<Vec<u8>> {
+        self.vm.current_state()
+            .map(|state| state.context.data.clone())
+            .unwrap_or_else(|| self.transaction.0.input.clone())
+    }
+    /// Frames of the transaction that have failed, in the order they failed.
+    /// Failures are only known for instructions that have been stepped
+    pub fn failures(&self) -> Vec<&Failure> {
+        self.failures.iter().map(|(_, f)| f).collect()
+    }
+
+    /// Event logs the transaction has emitted, in the order they were emitted.
+    /// Logs are only known for instructions that have been stepped. Logs of frames that have
+    /// failed, or whose callers have, are reverted and left out. Logs of frames that go on to
+    /// fail are included until they do
+    pub fn logs(&self) -> Vec<&EventLog> {
+        self.logs.iter()
+            .filter(|(step, log)| {
+                !self.reverts.iter().any(|(entered, failed, depth)| step > entered && step <= failed && log.depth >= *depth)
+            })
+            .map(|(_, l)| l)
+            .collect()
+    }
+
+    /// Log the state before every instruction stepped forward from now on, as the default tracer
+    /// of geth does. Instructions executed without stepping are not logged
+    pub fn start_trace(&mut self) {
+        if self.struct_logs.is_none() {
+            self.struct_logs = Some(Vec::new());
+        }
+    }
+
+    /// The trace of the instructions of the transaction stepped since tracing started.
+    /// `None` if not tracing
+    pub fn trace(&self) -> Option<Trace> {
+        let struct_logs = self.struct_logs.as_ref()?;
+        let gas: bigint::U256 = self.vm.used_gas().into();
+        Some(Trace {
+            gas: gas.low_u64(),
+            failed: match self.vm.status() {
+                VMStatus::ExitedErr(_) => true,
+                _ => false
+            },
+            return_value: super::write::hex(self.vm.out()),
+            struct_logs: struct_logs.iter().map(|(_, l)| l.clone()).collect(),
+        })
+    }
+
+    /// Call `observer` with every write to storage or memory made by an instruction stepped
+    /// forward. Instructions executed without stepping, or replayed when stepping back, are not observed
+    pub fn on_write<F>(&mut self, observer: F) where F: FnMut(&Write) + 'static {
+        self.observers.push(Box::new(observer));
+    }
+
     fn run_until(&mut self, opcode_pos: usize) -> Result<(), EmulError> {
         // If position is 0, we haven't started the VM yet
         while *self.positions.last().unwrap_or(&0) < opcode_pos {
@@ -128,6 +450,46 @@ impl<T> Emulator<T> where T: Transport {
         Ok(())
     }
 
+    /// The call depth of the currently executing machine, 0 for the transaction's own frame.
+    /// `None` if the VM has not been started
+    pub fn depth(&self) -> Option<usize> {
+        self.vm.current_state().map(frame_depth)
+    }
+
+    /// Gas left to the frame that is executing. `None` if the VM has not been started.
+    /// A frame that failed with an error other than `REVERT` forfeits all of its gas
+    pub fn gas(&self) -> Option<u64> {
+        let machine = self.vm.current_machine()?;
+        if let MachineStatus::ExitedErr(_) = machine.status() {
+            if self.opcode().ok()? != Opcode::REVERT {
+                return Some(0);
+            }
+        }
+        let gas: bigint::U256 = machine.state().available_gas().into();
+        Some(gas.low_u64())
+    }
+
+    /// Whether the next step executes an instruction, rather than entering a call or returning from one
+    pub fn executing(&self) -> bool {
+        match self.vm.current_machine().map(|m| m.status()) {
+            Some(MachineStatus::Running) => true,
+            _ => false
+        }
+    }
+
+    /// Whether the transaction has started executing
+    pub fn started(&self) -> bool {
+        self.vm.current_machine().is_some()
+    }
+
+    /// Whether the frame that is executing has finished, and returns to its caller with the next step
+    pub fn returning(&self) -> bool {
+        match self.vm.current_machine().map(|m| m.status()) {
+            Some(MachineStatus::ExitedOk) | Some(MachineStatus::ExitedErr(_)) | Some(MachineStatus::ExitedNotSupported(_)) => true,
+            _ => false
+        }
+    }
+
     pub fn finished(&self) -> bool {
         match self.vm.status() {
             VMStatus::Running => false,
@@ -149,7 +511,7 @@ impl<T> Emulator<T> where T: Transport {
     }
 
     /// Get the Current Runtime PC
-    pub fn pc(&self) -> Option<PC<ByzantiumPatch>> {
+    pub fn pc(&self) -> Option<PC<P>> {
         if let Some(mach) = self.vm.current_machine() {
             Some(mach.pc())
         } else {
@@ -163,45 +525,21 @@ impl<T> Emulator<T> where T: Transport {
         Ok(self.vm.current_machine().ok_or(EmulError::CouldNotAcquireVm)?.pc().position())
     }
 
+    /// The opcode that will be executed next
+    pub fn opcode(&self) -> Result<Opcode, EmulError> {
+        let pc = self.vm.current_machine().ok_or(EmulError::CouldNotAcquireVm)?.pc();
+        // running off the end of the code is an implicit STOP
+        Ok(pc.code().get(pc.position()).map(|op| Opcode::from(*op)).unwrap_or(Opcode::STOP))
+    }
+
     /// return the instruction position from an opcode offset
     pub fn instruction(&self) -> Result<usize, EmulError> {
         Ok(Self::into_instruction(self.offset()?, self.vm.current_machine().ok_or(EmulError::CouldNotAcquireVm)?.pc().code()))
     }
 
-    fn into_instruction(position: usize, code: &[u8]) -> usize {
-        let mut opcode_pos = 0;
-        let mut instruction_pos = 0;
-        'interpreter: loop {
-            let instruction = code[opcode_pos];
-            // check for CBOR-encoded AUXDATA first. this will always start with 0xa1
-            // this should be unique enough to avoid conflicts with other languages
-            // So far, AFAIK, Solidity is the only language that adds an extra 'metadata' portion
-            // to the end of runtime bytecode
-            match instruction {
-                0xa1 => {
-                    let code_slice = &code[opcode_pos..];
-                                                  // b     z     z      r     0
-                    if &code_slice[1..9] == &[0x65, 0x62, 0x7a, 0x7a, 0x72, 0x30, 0x58, 0x20] {
-                        break 'interpreter;
-                    } else {
-                        continue;
-                    }
-                }
-                _ => (),
-            };
-            match Opcode::from(instruction) {
-                Opcode::PUSH(bytes) => {
-                    opcode_pos += bytes + 1;
-                },
-                _ => {
-                    opcode_pos += 1;
-                },
-            }
-            if opcode_pos >= position {
-                break 'interpreter;
-            }
-            instruction_pos += 1;
-        }
+    /// the instruction position of the opcode at byte `position` in `code`
+    pub fn into_instruction(position: usize, code: &[u8]) -> usize {
+        let instruction_pos = disasm::instructions(code).take_while(|i| i.offset < position).count();
         debug!("Instruction Position {}", instruction_pos);
         instruction_pos
     }
@@ -221,10 +559,21 @@ impl<T> Emulator<T> where T: Transport {
     ///     input: Rc::new(get),
     ///     nonce: bigint::U256::zero(),
     /// };
-    /// emul.chain(tx_get, None);
+    /// emul.chain(tx_get, None)?;
     /// ```
-    pub fn chain(&mut self, tx: ValidTransaction, block: Option<HeaderParams>) {
+    pub fn chain(&mut self, tx: ValidTransaction, block: Option<HeaderParams>) -> Result<(), EmulError> {
+        self.persist_balance_increases()?;
         self.positions.clear();
+        self.checkpoints.clear();
+        self.frames.clear();
+        self.returns.clear();
+        self.failures.clear();
+        self.reverts.clear();
+        self.logs.clear();
+        if let Some(logs) = self.struct_logs.as_mut() {
+            logs.clear();
+        }
+        self.traced_storage.clear();
         if let Some(new_head) = block {
             self.transaction = (tx.clone(), new_head.clone());
             self.vm = sputnikvm::TransactionVM::new(tx, new_head);
@@ -233,6 +582,8 @@ impl<T> Emulator<T> where T: Transport {
             let (txinfo, block) = self.transaction.clone();
             self.vm = sputnikvm::TransactionVM::new(txinfo, block);
         }
+        self.checkpoint();
+        Ok(())
     }
 
     /// Access the underyling vm implementation directly via the predicate F
@@ -245,59 +596,148 @@ impl<T> Emulator<T> where T: Transport {
     /// ```
     pub fn read_raw<F>(&self, mut fun: F) -> Result<(), Error>
     where
-        F: FnMut(&SeqTransactionVM<ByzantiumPatch>) -> Result<(), Error>
+        F: FnMut(&SeqTransactionVM<P>) -> Result<(), Error>
     {
         fun(&self.vm)
     }
 
+    /// restores the nearest checkpoint preceding the last step, and replays only the instructions
+    /// that were executed after it
     fn step_back(&mut self) -> Result<(), EmulError> {
-        let mut last_pos = 0;
-        let (txinfo, header) = self.transaction.clone();
-        let new_vm = sputnikvm::TransactionVM::new(txinfo, header);
-        std::mem::replace(&mut self.vm, new_vm);
+        self.positions.pop();
+        let target = self.positions.len();
+        // any checkpoints taken or failures seen after the target are no longer valid
+        self.failures.retain(|(step, _)| *step <= target);
+        self.reverts.retain(|(_, step, _)| *step <= target);
+        self.logs.retain(|(step, _)| *step <= target);
+        if let Some(logs) = self.struct_logs.as_mut() {
+            logs.retain(|(step, _)| *step <= target);
+            self.traced_storage = logs.iter().map(|(_, l)| (l.address, l.storage.clone())).collect();
+        }
+        while self.checkpoints.last().map(|c| c.step > target).unwrap_or(false) {
+            self.checkpoints.pop();
+        }
+        let (step, vm, cache, frames, returns) = {
+            let nearest = self.checkpoints.last().expect("Initial checkpoint is never removed; qed");
+            (nearest.step, nearest.vm.clone(), nearest.state_cache.clone(), nearest.frames.clone(), nearest.returns.clone())
+        };
+        trace!("Restoring checkpoint at step {}, replaying {} steps", step, target - step);
+        self.vm = vm;
+        *self.state_cache.borrow_mut() = cache;
+        self.frames = frames;
+        self.returns = returns;
 
-        // run the vm until the latest stored position
-        while last_pos < *self.positions.last().unwrap_or(&0) {
+        for _ in step..target {
             self.step()?;
-            if let Some(x) = self.vm.current_machine() {
-                last_pos = x.pc().position();
-            } else {
-                panic!("Vm stepped but state is not initialized");
-            }
         }
         Ok(())
     }
 
     fn step_forward(&mut self) -> Result<(), EmulError> {
+        let depth = self.depth();
+        let write = if self.observers.is_empty() {
+            None
+        } else {
+            self.require_pending_storage()?;
+            self.pending_write()
+        };
+        let revert = self.pending_revert();
+        let log = self.pending_log();
+        let struct_log = if self.struct_logs.is_some() && self.executing() { self.struct_log() } else { None };
         self.step()?;
+        if let Some(write) = write {
+            // return data is written once the frame the call entered returns
+            if self.depth() > depth {
+                self.returns.push(write);
+            } else {
+                self.observe(write, depth);
+            }
+        }
+        loop {
+            match self.returns.last() {
+                Some(Write::Memory { depth: d, .. }) if Some(*d) == self.depth() => (),
+                _ => break
+            }
+            let write = self.returns.pop().expect("matched the last write; qed");
+            let depth = self.depth();
+            self.observe(write, depth);
+        }
+        let failure = revert.or_else(|| self.failure());
+        if let Some(mut struct_log) = struct_log {
+            struct_log.gas_cost = struct_log.gas.saturating_sub(self.gas().unwrap_or(0));
+            struct_log.error = failure.as_ref().map(|f| f.kind.to_string());
+            self.traced_storage.insert(struct_log.address, struct_log.storage.clone());
+            let step = self.positions.len() + 1;
+            if let Some(logs) = self.struct_logs.as_mut() {
+                logs.push((step, struct_log));
+            }
+        }
+        if let Some(failure) = failure {
+            // the frame that failed is only left on the next step
+            let entered = self.frames.get(failure.depth).map(|f| f.entered).unwrap_or(0);
+            self.reverts.push((entered, self.positions.len() + 1, failure.depth));
+            self.failures.push((self.positions.len() + 1, failure));
+        }
+        // a `LOG` that fails emits nothing
+        if let Some(log) = log.filter(|_| self.failure().is_none()) {
+            self.logs.push((self.positions.len() + 1, log));
+        }
         if let Some(x) = self.vm.current_machine() {
             self.positions.push(x.pc().position());
         } else {
             self.positions.push(0);
         }
-
+        // entering or leaving a call is a natural place to snapshot
+        if self.positions.len() % CHECKPOINT_INTERVAL == 0 || depth != self.depth() {
+            self.checkpoint();
+        }
         Ok(())
     }
 
+    /// take a snapshot of the current VM and state cache
+    fn checkpoint(&mut self) {
+        let step = self.positions.len();
+        if self.checkpoints.last().map(|c| c.step == step).unwrap_or(false) {
+            return;
+        }
+        self.checkpoints.push(Checkpoint {
+            step,
+            vm: self.vm.clone(),
+            state_cache: self.state_cache.borrow().clone(),
+            frames: self.frames.clone(),
+            returns: self.returns.clone(),
+        });
+    }
+
     fn run(&mut self) -> Result<(), EmulError> {
+        let running = !self.finished();
         'run: loop {
             let result = self.vm.fire();
             self.persist()?;
-            if handle_requires(&result, self.state_cache.clone(), &mut self.vm, &self.client)? {
+            if handle_requires(&result, self.state_cache.clone(), &mut self.vm, &self.client, &self.state_block)? {
                 break 'run;
             }
         }
+        // frames entered and left while running are never seen, but the ones left on the call
+        // stack once it pauses are
+        self.update_frames(None);
+        self.returns.clear();
+        // only the failure of the transaction's own frame is seen once it has run to the end
+        if let Some(failure) = self.failure().filter(|_| running) {
+            self.failures.push((self.positions.len(), failure));
+        }
         Ok(())
     }
 
     /// steps the vm, querying node for any information that the VM needs
     /// vm returns true when execution is finished
     fn step(&mut self) -> Result<(), EmulError> {
+        let call = self.pending_call();
         let mut res = self.vm.step();
         trace!("VM Step {:?}", res);
         self.persist()?;
         'require: loop {
-            let req = handle_requires(&res, self.state_cache.clone(), &mut self.vm, &self.client)?;
+            let req = handle_requires(&res, self.state_cache.clone(), &mut self.vm, &self.client, &self.state_block)?;
             if req {
                 break 'require;
             } else {
@@ -305,15 +745,213 @@ impl<T> Emulator<T> where T: Transport {
                 self.persist()?;
             }
         }
+        self.update_frames(call);
         Ok(())
     }
 
+    /// The failure of the frame if the next instruction is a `REVERT`, with the data it returns
+    fn pending_revert(&self) -> Option<Failure> {
+        let pc = self.vm.current_machine()?.pc();
+        if Opcode::from(*pc.code().get(pc.position())?) != Opcode::REVERT {
+            return None;
+        }
+        let state = self.vm.current_state()?;
+        let data = self.read_memory(state.stack.peek(0).ok()?.0, state.stack.peek(1).ok()?.0).unwrap_or_default();
+        let frame = self.frame()?;
+        Some(Failure {
+            kind: FailureKind::Revert(data),
+            depth: frame_depth(state),
+            code_address: frame.code_address,
+            code: frame.code.clone(),
+            pc: pc.position(),
+        })
+    }
+
+    /// The log the next instruction emits, if it is a `LOG`
+    fn pending_log(&self) -> Option<EventLog> {
+        let pc = self.vm.current_machine()?.pc();
+        let topics = match Opcode::from(*pc.code().get(pc.position())?) {
+            Opcode::LOG(n) => n,
+            _ => return None
+        };
+        let state = self.vm.current_state()?;
+        let frame = self.frame()?;
+        Some(EventLog {
+            address: state.context.address,
+            topics: (2..2 + topics).map(|i| state.stack.peek(i).ok().map(|word: bigint::M256| bigint::H256::from(word.0))).collect::<Option<_>>()?,
+            data: self.read_memory(state.stack.peek(0).ok()?.0, state.stack.peek(1).ok()?.0)?,
+            depth: frame_depth(state),
+            code: frame.code.clone(),
+            pc: pc.position(),
+        })
+    }
+
+    // the state before the next instruction, as it is traced
+    fn struct_log(&self) -> Option<StructLog> {
+        let machine = self.vm.current_machine()?;
+        let state = machine.state();
+        let opcode = self.opcode().ok()?;
+        let address = state.context.address;
+        let peek = |i| state.stack.peek(i).ok().map(|word: bigint::M256| word.0);
+        let mut storage = self.traced_storage.get(&address).cloned().unwrap_or_default();
+        match (opcode, peek(0), peek(1)) {
+            (Opcode::SLOAD, Some(slot), _) => {
+                if let Ok(value) = self.frame_storage_at(address, slot) {
+                    storage.insert(trace::word(slot), trace::word(value.0));
+                }
+            },
+            (Opcode::SSTORE, Some(slot), Some(value)) => {
+                storage.insert(trace::word(slot), trace::word(value));
+            },
+            _ => ()
+        }
+        let memory = (0..state.memory.len())
+            .step_by(32)
+            .map(|start| super::write::hex(&(start..start + 32).map(|i| state.memory.read_raw(i.into())).collect::<Vec<u8>>()))
+            .collect();
+        Some(StructLog {
+            pc: machine.pc().position(),
+            op: trace::op_name(opcode),
+            gas: self.gas()?,
+            gas_cost: 0,
+            depth: frame_depth(state) + 1,
+            error: None,
+            stack: (0..state.stack.len()).rev().filter_map(peek).map(trace::word).collect(),
+            memory,
+            storage,
+            address,
+        })
+    }
+
+    // up to `len` bytes of the memory of the current frame from `offset`.
+    // `None` for an offset too large to be written without running out of gas
+    fn read_memory(&self, offset: bigint::U256, len: bigint::U256) -> Option<Vec<u8>> {
+        let state = self.vm.current_state()?;
+        if offset > bigint::U256::from(u32::max_value() as u64) {
+            return None;
+        }
+        let offset = offset.as_usize();
+        let len = len.min(bigint::U256::from(MAX_MEMORY_READ)).as_usize();
+        Some((offset..offset + len).map(|i| state.memory.read_raw(i.into())).collect())
+    }
+
+    /// The failure of the frame that is executing, if the instruction just stepped failed.
+    /// A frame that failed stays on the call stack until the next step
+    fn failure(&self) -> Option<Failure> {
+        let machine = self.vm.current_machine()?;
+        let kind = match machine.status() {
+            // a frame that reverted keeps the data it returned
+            MachineStatus::ExitedErr(OnChainError::Revert) => FailureKind::Revert(machine.state().out.to_vec()),
+            MachineStatus::ExitedErr(err) => FailureKind::from(err),
+            _ => return None
+        };
+        let frame = self.frame()?;
+        Some(Failure {
+            kind,
+            depth: frame_depth(machine.state()),
+            code_address: frame.code_address,
+            code: frame.code.clone(),
+            pc: machine.pc().position(),
+        })
+    }
+
+    /// The write the next instruction will make, and the value it replaces.
+    /// The bytes written to memory are only known once the instruction has executed, or, for
+    /// the return data of a call, once the frame it enters has returned
+    fn pending_write(&self) -> Option<Write> {
+        let pc = self.vm.current_machine()?.pc();
+        let opcode = Opcode::from(*pc.code().get(pc.position())?);
+        let state = self.vm.current_state()?;
+        let peek = |i| state.stack.peek(i).ok().map(|word: bigint::M256| word.0);
+        let (offset, len) = match opcode {
+            Opcode::SSTORE => {
+                let slot = peek(0)?;
+                let old = state.account_state.storage_read(state.context.address, slot).ok()?;
+                let new = state.stack.peek(1).ok()?;
+                return Some(Write::Storage { address: state.context.address, slot, old, new });
+            },
+            Opcode::MSTORE => (peek(0)?, bigint::U256::from(32)),
+            Opcode::MSTORE8 => (peek(0)?, bigint::U256::one()),
+            Opcode::CALLDATACOPY | Opcode::CODECOPY | Opcode::RETURNDATACOPY => (peek(0)?, peek(2)?),
+            Opcode::EXTCODECOPY => (peek(1)?, peek(3)?),
+            Opcode::CALL | Opcode::CALLCODE => (peek(5)?, peek(6)?),
+            Opcode::DELEGATECALL | Opcode::STATICCALL => (peek(4)?, peek(5)?),
+            _ => return None
+        };
+        let old = self.read_memory(offset, len)?;
+        Some(Write::Memory { depth: frame_depth(state), offset: offset.as_usize(), old, new: Vec::new() })
+    }
+
+    // pass a write made by the instruction just stepped to the observers. Nothing is written if
+    // the instruction failed, which leaves the frame it was executed in
+    fn observe(&mut self, write: Write, depth: Option<usize>) {
+        let write = match write {
+            Write::Memory { depth: d, offset, old, .. } => {
+                let state = match self.vm.current_state() {
+                    Some(s) if Some(frame_depth(s)) == depth => s,
+                    _ => return
+                };
+                let new = (offset..offset + old.len()).map(|i| state.memory.read_raw(i.into())).collect();
+                Write::Memory { depth: d, offset, old, new }
+            },
+            storage => {
+                if self.depth() != depth {
+                    return;
+                }
+                storage
+            }
+        };
+        for observer in self.observers.iter_mut() {
+            observer(&write);
+        }
+    }
+
+    /// If the next instruction enters a new frame, its kind, the address it calls and its position
+    fn pending_call(&self) -> Option<(FrameKind, Option<bigint::H160>, usize)> {
+        let pc = self.vm.current_machine()?.pc();
+        let kind = FrameKind::from_opcode(Opcode::from(*pc.code().get(pc.position())?))?;
+        // the callee is the second item on the stack for every kind of call
+        let target = match kind {
+            FrameKind::Create => None,
+            _ => self.vm.current_state()?.stack.peek(1).ok().map(|addr| addr.into())
+        };
+        Some((kind, target, pc.position()))
+    }
+
+    /// Push or pop frames so that there is one for every machine on the call stack of the VM
+    fn update_frames(&mut self, call: Option<(FrameKind, Option<bigint::H160>, usize)>) {
+        let root_kind = if self.is_creation() { FrameKind::Create } else { FrameKind::Call };
+        let state = match self.vm.current_state() {
+            Some(s) => s,
+            None => return
+        };
+        self.frames.truncate(frame_depth(state) + 1);
+        if self.frames.len() != frame_depth(state) {
+            return;
+        }
+        let (kind, target, call_site) = match call {
+            Some((kind, target, position)) if !self.frames.is_empty() => (kind, target, Some(position)),
+            _ => (root_kind, None, None)
+        };
+        trace!("Entering frame at depth {} for {:#x}", frame_depth(state), state.context.address);
+        self.frames.push(Frame {
+            kind,
+            address: state.context.address,
+            code_address: target.unwrap_or(state.context.address),
+            code: state.context.code.clone(),
+            // the transaction's own frame is entered before anything is stepped, including when
+            // its first step is replayed
+            entered: if call_site.is_some() { self.positions.len() } else { 0 },
+            call_site,
+        });
+    }
+
     /// persists any account storage that has been changed by the currently executing transaction up until the point in execution
     /// used when chaining transactions during debugging
     /// for example
     ///
     /// ```rust,no_run
-    /// let emulator = Emulator::new(tx_set, header, client);
+    /// let emulator = Emulator::<_, ByzantiumPatch>::new(tx_set, header, client);
     /// emulator.fire(Action::Exec);
     /// emulator.chain(tx_get);
     /// emulator.fire(Action::Exec);
@@ -325,33 +963,26 @@ impl<T> Emulator<T> where T: Transport {
         let res = self.vm.accounts().map(|acc| {
             match acc {
                 AccountChange::Full {nonce, address, balance, changing_storage, code} => {
-                    if changing_storage.len() > 0 && self.state_cache.borrow().contains_key(&address) {
-                        self.state_cache.borrow_mut()
-                            .get_mut(&address)
-                            .expect("Scope is conditional; qed")
-                            .merge(changing_storage.clone());
-                        Ok(())
-                    } else if changing_storage.len() > 0 { // if we don't have the key in our account cache yet
-                        self.state_cache.borrow_mut().insert(address.clone(), Account {
-                            nonce: nonce.clone(),
-                            balance: balance.clone(),
-                            code: code.clone(),
-                            storage: changing_storage.clone().into()
-                        });
-                        Ok(())
-                    } else { // if there is no storage to update, don't bother
-                        Ok(())
+                    let mut cache = self.state_cache.borrow_mut();
+                    if let Some(account) = cache.get_mut(address) {
+                        account.nonce = *nonce;
+                        account.balance = *balance;
+                        account.code = code.clone();
+                        account.merge(changing_storage.clone());
+                        return Ok(());
                     }
-                },
-                AccountChange::IncreaseBalance(addr, amnt) => {
-                    self.state_cache
-                        .borrow_mut()
-                        .get_mut(&addr)
-                        .ok_or(EmulError::State(StateError::NotFound(*addr)))?
-                        .balance
-                        .overflowing_add(*amnt);
+                    // if we don't have the key in our account cache yet
+                    cache.insert(*address, Account {
+                        nonce: *nonce,
+                        balance: *balance,
+                        code: code.clone(),
+                        storage: changing_storage.clone().into()
+                    });
                     Ok(())
                 },
+                // balance increases are relative, so they are only persisted once the transaction is over
+                // (see `persist_balance_increases`)
+                AccountChange::IncreaseBalance(_, _) => Ok(()),
                 // Create assumes the account does not yet exist, so this will replace anything that bychance exists already locally
                 AccountChange::Create {nonce, address, balance, storage, code} => {
                     self.state_cache.borrow_mut().insert(address.clone(), Account {
@@ -373,38 +1004,63 @@ impl<T> Emulator<T> where T: Transport {
         }).collect::<Result<(), EmulError>>();
         res
     }
+
+    /// adds the balance increases of the current transaction to the local cache.
+    /// Must only be called once per transaction, before it is replaced
+    fn persist_balance_increases(&self) -> Result<(), EmulError> {
+        for acc in self.vm.accounts() {
+            if let AccountChange::IncreaseBalance(addr, amnt) = acc {
+                if !self.state_cache.borrow().contains_key(addr) {
+                    let account = fetch_account(*addr, &self.client, &self.state_block)?;
+                    self.state_cache.borrow_mut().insert(*addr, account);
+                }
+                let mut cache = self.state_cache.borrow_mut();
+                let account = cache.get_mut(addr).expect("Account inserted if it did not exist; qed");
+                account.balance = account.balance + *amnt;
+            }
+        }
+        Ok(())
+    }
 }
 
 
-fn handle_requires<T>(
+// the VM counts the transaction's own frame as depth 1
+fn frame_depth<M, P: Patch>(state: &State<M, P>) -> usize {
+    state.depth - 1
+}
+
+fn handle_requires<T, P>(
     result: &Result<(), RequireError>,
     cache: Rc<RefCell<HashMap<bigint::H160, Account>>>,
-    vm: &mut SeqTransactionVM<ByzantiumPatch>,
-    client: &Web3<T>) -> Result<bool, EmulError>
+    vm: &mut SeqTransactionVM<P>,
+    client: &Web3<T>,
+    block: &BlockNumber) -> Result<bool, EmulError>
 where
-    T: Transport
+    T: Transport,
+    P: Patch
 {
     match result.clone() {
         Ok(()) => {
             Ok(true)
         },
         Err(RequireError::Account(addr)) => {
-            info!("Acquiring balance, code, and nonce of account {:#x} for VM", addr);
-            let nonce = client.eth().transaction_count(ethereum_types::H160(addr.0), Some(BlockNumber::Latest)).wait()?;
-            debug!("Nonce: {:#x}", nonce);
-            let balance: U256 = client.eth().balance(ethereum_types::H160(addr.0), Some(BlockNumber::Latest)).wait()?; // U256
-            debug!("Balance: {:#x}", balance);
-            let mut code = client.eth().code(ethereum_types::H160(addr.0), Some(BlockNumber::Latest)).wait(); // Bytes
-            debug!("Code: {:x?}", code);
-            if code.is_err() {
-                code = Ok(Bytes(vec![0]));
-            }
-            let code = code.unwrap();
+            // accounts changed by a previous transaction must be read from the local cache
+            let account = match cache.borrow().get(&addr) {
+                Some(acc) => {
+                    trace!("Found account {:#x} in Local Cache", addr);
+                    Some(acc.clone())
+                },
+                None => None
+            };
+            let account = match account {
+                Some(acc) => acc,
+                None => fetch_account(addr, client, block)?
+            };
             vm.commit_account(AccountCommitment::Full {
-                nonce: bigint::U256(nonce.0),
+                nonce: account.nonce,
                 address: addr,
-                balance: bigint::U256(balance.0),
-                code: Rc::new(code.0),
+                balance: account.balance,
+                code: account.code,
             })?;
             Ok(false)
         },
@@ -427,7 +1083,7 @@ where
                 });
                 Ok(false)
             } else {
-                let value = client.eth().storage(ethereum_types::H160(addr.0), ethereum_types::U256(index.0), Some(BlockNumber::Latest)).wait()?;
+                let value = client.eth().storage(ethereum_types::H160(addr.0), ethereum_types::U256(index.0), Some(*block)).wait()?;
                 debug!("Committing account {:#x} with storage at {:#x} that is {:#x} to VM", addr, index, value);
                 vm.commit_account(AccountCommitment::Storage {
                     address: addr,
@@ -440,29 +1096,60 @@ where
         },
         Err(RequireError::AccountCode(addr)) => {
             info!("Acquiring code at {:#x} for VM", addr);
-            let code: Bytes = client.eth().code(ethereum_types::H160(addr.0), Some(BlockNumber::Latest)).wait()?;
+            let cached = cache.borrow().get(&addr).map(|acc| acc.code.clone());
+            let code = match cached {
+                Some(code) => code,
+                None => {
+                    let code: Bytes = client.eth().code(ethereum_types::H160(addr.0), Some(*block)).wait()?;
+                    Rc::new(code.0)
+                }
+            };
             vm.commit_account(AccountCommitment::Code {
                 address: addr,
-                code: Rc::new(code.0)
+                code,
             })?;
             Ok(false)
         },
-        // the debugger is useless if execution cannot continue
-        Err(err) => {
-            error!("VM execution failed, unknown require! {:?}", err);
-            panic!("VM Execution failed, unknown require");
-        }
+        Err(RequireError::Blockhash(number)) => {
+            info!("Acquiring the hash of block {} for VM", number);
+            let block = node::block(client, BlockId::Number(BlockNumber::Number(number.as_u64())))?;
+            let hash = block.hash.ok_or_else(|| EmulError::BlockNotFound(format!("{}", number)))?;
+            vm.commit_blockhash(number, bigint::H256(hash.0))?;
+            Ok(false)
+        },
     }
 }
 
 
+/// get the balance, code, and nonce of an account from the node
+fn fetch_account<T>(addr: bigint::H160, client: &Web3<T>, block: &BlockNumber) -> Result<Account, EmulError> where T: Transport {
+    info!("Acquiring balance, code, and nonce of account {:#x} for VM", addr);
+    let nonce = client.eth().transaction_count(ethereum_types::H160(addr.0), Some(*block)).wait()?;
+    debug!("Nonce: {:#x}", nonce);
+    let balance: U256 = client.eth().balance(ethereum_types::H160(addr.0), Some(*block)).wait()?; // U256
+    debug!("Balance: {:#x}", balance);
+    let mut code = client.eth().code(ethereum_types::H160(addr.0), Some(*block)).wait(); // Bytes
+    debug!("Code: {:x?}", code);
+    if code.is_err() {
+        code = Ok(Bytes(vec![0]));
+    }
+    let code = code.unwrap();
+    Ok(Account {
+        nonce: bigint::U256(nonce.0),
+        balance: bigint::U256(balance.0),
+        storage: HashMap::new(),
+        code: Rc::new(code.0),
+    })
+}
 
 #[cfg(test)]
 mod test {
     use speculate::speculate;
     use bigint::{Address, Gas};
     use sputnikvm::TransactionAction;
+    use sputnikvm_network_foundation::ByzantiumPatch;
     use super::*;
+    use std::str::FromStr;
     use edb_test_helpers as edbtest;
 
     speculate! {
@@ -504,7 +1191,7 @@ mod test {
                     gas_limit: bigint::Gas::from(80000000 as u64)
                 };
                 // make this into a macro
-                let mut emul = Emulator::new(tx_set, headers, client);
+                let mut emul = Emulator::<_, ByzantiumPatch>::with_header(tx_set, headers, BlockNumber::Number(6285997), client);
                 // let code: Vec<u8> = hex::decode(simple).unwrap();
             }
 
@@ -512,6 +1199,94 @@ mod test {
                 emul.fire(Action::Exec).unwrap();
             }
 
+            it "can change the stack while paused" {
+                for _ in 0..3 {
+                    emul.fire(Action::StepForward).unwrap();
+                }
+                emul.set_stack(0, bigint::M256::from(7u64)).unwrap();
+                emul.read_raw(|vm| {
+                    assert_eq!(vm.current_state().unwrap().stack.peek(0).unwrap(), bigint::M256::from(7u64));
+                    Ok(())
+                }).unwrap();
+                assert!(emul.set_stack(2, bigint::M256::zero()).is_err());
+            }
+
+            it "can change memory while paused" {
+                emul.fire(Action::StepForward).unwrap();
+                while emul.memory().unwrap().len() == 0 {
+                    emul.fire(Action::StepForward).unwrap();
+                }
+                emul.set_memory(1, &[7, 8]).unwrap();
+                let memory = emul.memory().unwrap();
+                assert_eq!((0..4).map(|i| memory.read_raw(i.into())).collect::<Vec<u8>>(), vec![0, 7, 8, 0]);
+                // memory cannot grow this way
+                let len = memory.len();
+                assert!(emul.set_memory(len - 1, &[1, 2]).is_err());
+                assert!(emul.set_memory(usize::max_value(), &[1]).is_err());
+            }
+
+            it "can change storage while paused" {
+                let address = edbtest::bigint_addr(edbtest::SIMPLE_STORAGE_ADDR);
+                let slot = bigint::U256::one();
+                assert!(emul.set_storage(slot, bigint::M256::from(5u64)).is_err());
+                emul.fire(Action::StepForward).unwrap();
+                emul.set_storage(slot, bigint::M256::from(5u64)).unwrap();
+                assert_eq!(emul.frame_storage_at(address, slot).unwrap(), bigint::M256::from(5u64));
+                // the change is kept when execution continues, and when stepping back to it
+                emul.fire(Action::StepForward).unwrap();
+                emul.fire(Action::StepBack).unwrap();
+                assert_eq!(emul.frame_storage_at(address, slot).unwrap(), bigint::M256::from(5u64));
+                emul.fire(Action::Exec).unwrap();
+                assert_eq!(emul.storage_at(address, slot).unwrap(), bigint::M256::from(5u64));
+            }
+
+            it "reads storage as the executing frame has written it" {
+                let address = edbtest::bigint_addr(edbtest::SIMPLE_STORAGE_ADDR);
+                emul.fire(Action::StepForward).unwrap();
+                while emul.opcode().unwrap() != Opcode::SSTORE {
+                    emul.fire(Action::StepForward).unwrap();
+                }
+                let slot = bigint::U256::zero();
+                assert_eq!(emul.frame_storage_at(address, slot).unwrap(), emul.storage_at(address, slot).unwrap());
+                emul.fire(Action::StepForward).unwrap();
+                // `set` is called with 0x1337
+                assert_eq!(emul.frame_storage_at(address, slot).unwrap(), bigint::M256::from(0x1337u64));
+            }
+
+            it "reports storage writes with the value they replace" {
+                let address = edbtest::bigint_addr(edbtest::SIMPLE_STORAGE_ADDR);
+                let (slot, value) = (bigint::U256::zero(), bigint::M256::from(42u64));
+                emul.override_state(address, &StateOverride::default().with_storage(slot, value)).unwrap();
+                let writes = Rc::new(RefCell::new(Vec::new()));
+                let observed = writes.clone();
+                emul.on_write(move |write| observed.borrow_mut().push(write.clone()));
+                while !emul.finished() {
+                    emul.fire(Action::StepForward).unwrap();
+                }
+                let stored = writes.borrow().iter().filter_map(|w| match w {
+                    Write::Storage { old, new, .. } => Some((*old, *new)),
+                    _ => None
+                }).collect::<Vec<_>>();
+                assert_eq!(stored, vec![(value, bigint::M256::from(0x1337u64))]);
+            }
+
+            it "reads block hashes from the node" {
+                let number = bigint::U256::from(edbtest::MINED_BLOCK_NUMBER);
+                let require = Err(RequireError::Blockhash(number));
+                assert!(!handle_requires(&require, emul.state_cache.clone(), &mut emul.vm, &emul.client, &emul.state_block).unwrap());
+                // the hash is committed once
+                assert!(emul.vm.commit_blockhash(number, bigint::H256::default()).is_err());
+            }
+
+            it "can override state before starting" {
+                let address = edbtest::bigint_addr(edbtest::SIMPLE_STORAGE_ADDR);
+                let (slot, value) = (bigint::U256::zero(), bigint::M256::from(42u64));
+                emul.override_state(address, &StateOverride::default().with_storage(slot, value)).unwrap();
+                assert_eq!(emul.storage_at(address, slot).unwrap(), value);
+                emul.fire(Action::StepForward).unwrap();
+                assert!(emul.override_state(address, &StateOverride::default()).is_err());
+            }
+
             it "can step forward" {
                 emul.read_raw(|vm| {
                     assert_eq!(vm.current_machine().is_none(), true);
@@ -546,6 +1321,55 @@ mod test {
                 }).unwrap();
             }
 
+            it "can step backward repeatedly" {
+                let mut positions = Vec::new();
+                for _ in 0..10 {
+                    emul.fire(Action::StepForward).unwrap();
+                    emul.read_raw(|vm| {
+                        positions.push(vm.current_state().unwrap().position);
+                        Ok(())
+                    }).unwrap();
+                }
+                for _ in 0..5 {
+                    emul.fire(Action::StepBack).unwrap();
+                }
+                emul.read_raw(|vm| {
+                    assert_eq!(positions[4], vm.current_state().unwrap().position);
+                    Ok(())
+                }).unwrap();
+            }
+
+            it "tracks the frame of the transaction" {
+                emul.fire(Action::StepForward).unwrap();
+                emul.fire(Action::StepForward).unwrap();
+                emul.fire(Action::StepBack).unwrap();
+                assert_eq!(emul.frames().len(), 1);
+                let frame = emul.frame().unwrap();
+                assert_eq!(frame.address, edbtest::bigint_addr(edbtest::SIMPLE_STORAGE_ADDR));
+                assert_eq!(frame.kind, FrameKind::Call);
+                assert!(frame.call_site.is_none());
+            }
+
+            it "tracks the frame of the transaction when running without stepping" {
+                emul.fire(Action::Exec).unwrap();
+                assert_eq!(emul.frames().len(), 1);
+                assert_eq!(emul.frame().unwrap().address, edbtest::bigint_addr(edbtest::SIMPLE_STORAGE_ADDR));
+            }
+
+            it "records the failure of a transaction run without stepping" {
+                // no function has this selector
+                let unknown = ValidTransaction { input: Rc::new(vec![0xde, 0xad, 0xbe, 0xef]), ..tx_get };
+                // the VM only pays the miner of a failed transaction if it has the account already
+                let mut header = emul.transaction.1.clone();
+                header.beneficiary = edbtest::bigint_addr(edbtest::ADDR_CALLER);
+                emul.fire(Action::Exec).unwrap();
+                assert!(emul.failures().is_empty());
+                emul.chain(unknown, Some(header)).unwrap();
+                emul.fire(Action::Exec).unwrap();
+                assert_eq!(emul.failures().len(), 1);
+                assert_eq!(emul.failures()[0].depth, 0);
+            }
+
             it "can execute the entire program" {
                 emul.fire(Action::Exec).unwrap();
                 emul.read_raw(|vm| {
@@ -563,19 +1387,56 @@ mod test {
 
             it "should chain, persisting account storage" {
                 emul.fire(Action::Exec).unwrap();
-                emul.chain(tx_get, None);
+                emul.chain(tx_get, None).unwrap();
                 emul.fire(Action::Exec).unwrap();
                 let out = emul.output();
                 assert_eq!(U256::from("1337"), U256::from(out.as_slice()));
             }
 
+            it "should chain, persisting the fee paid to the miner" {
+                emul.fire(Action::Exec).unwrap();
+                let fee: bigint::U256 = emul.vm.used_gas().into();
+                emul.chain(tx_get, None).unwrap();
+                let miner = edbtest::bigint_addr(edbtest::MINER);
+                // the mock gives every account a balance of 150,000,000 wei, and the gas price is 1
+                assert_eq!(emul.state_cache.borrow()[&miner].balance, bigint::U256::from(150_000_000u64) + fee);
+            }
+
             it "should get an instruction offset from opcode offset" {
                 // fn into_instruction(position: usize, code: &[u8]) -> usize {
                 // PUSH1 0x80 PUSH3 0x20 0x31 0x32 ADD DIV PUSH6 0x10 0x40 0x10 0x10 0x70 0x23
                 let code: [u8; 15] = [0x60, 0x80, 0x62, 0x20, 0x31, 0x32, 0x01, 0x04, 0x65, 0x10, 0x40, 0x10, 0x10, 0x70, 0x23];
-                let offset = Emulator::<edbtest::MockWeb3Transport>::into_instruction(7, &code);
+                let offset = Emulator::<edbtest::MockWeb3Transport, ByzantiumPatch>::into_instruction(7, &code);
                 assert_eq!(offset, 3)
             }
         }
+
+        describe "replay" {
+            before {
+                pretty_env_logger::try_init();
+                let client = web3::Web3::new(edbtest::MockWeb3Transport::default());
+                let hash = |h: &str| H256::from_str(&h[2..]).unwrap();
+            }
+
+            it "executes the transactions before it in its block first" {
+                // `set(1337)` comes before `get()` in the mined block
+                let mut emul = Emulator::<_, ByzantiumPatch>::replay(hash(edbtest::MINED_GET_TX), client).unwrap();
+                assert_eq!(emul.state_block(), BlockNumber::Number(edbtest::MINED_BLOCK_NUMBER - 1));
+                assert_eq!(emul.header().number, bigint::U256::from(edbtest::MINED_BLOCK_NUMBER));
+                emul.fire(Action::Exec).unwrap();
+                assert_eq!(U256::from(1337), U256::from(emul.output().as_slice()));
+            }
+
+            it "replays the first transaction of a block against the state before the block" {
+                let mut emul = Emulator::<_, ByzantiumPatch>::replay(hash(edbtest::MINED_SET_TX), client).unwrap();
+                emul.fire(Action::Exec).unwrap();
+                let slot = emul.storage_at(edbtest::bigint_addr(edbtest::SIMPLE_STORAGE_ADDR), bigint::U256::zero()).unwrap();
+                assert_eq!(slot, bigint::M256::from(1337u64));
+            }
+
+            it "fails for transactions the node does not know" {
+                assert!(Emulator::<_, ByzantiumPatch>::replay(H256::zero(), client).is_err());
+            }
+        }
     }
 }
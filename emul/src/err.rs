@@ -13,7 +13,19 @@ pub enum EmulError {
     #[fail(display = "An error occurred storing or retrieving data for an ethereum account from local storage {}", _0)]
     State( #[fail(cause)] StateError),
     #[fail(display = "Could not acquire current VM. is the VM started?")]
-    CouldNotAcquireVm
+    CouldNotAcquireVm,
+    #[fail(display = "Block {} not found", _0)]
+    BlockNotFound(String),
+    #[fail(display = "Transaction {:#x} not found", _0)]
+    TransactionNotFound(web3::types::H256),
+    #[fail(display = "Transaction {:#x} has not been mined", _0)]
+    TransactionPending(web3::types::H256),
+    #[fail(display = "State can only be overridden before the transaction starts")]
+    AlreadyStarted,
+    #[fail(display = "Execution must be paused at an instruction to change its state")]
+    NotPaused,
+    #[fail(display = "Cannot change the state: {}", _0)]
+    Mutation(String),
 }
 
 /// Errors that occured while interacting with In-Memory or cached Ethereum State Storage
@@ -21,6 +33,8 @@ pub enum EmulError {
 pub enum StateError {
     #[fail(display = "IO Error")]
     Io(std::io::Error),
+    #[fail(display = "Could not decode stored data: {}", _0)]
+    Decoder(serde_json::Error),
     #[fail(display = "Could not find account entry corresponding to {}", _0)]
     NotFound(bigint::H160),
 }
@@ -47,6 +61,8 @@ impl From<web3::Error> for EmulError {
 pub enum VmError {
     #[fail(display = "Commit {:?}", _0)]
     Commit(sputnikvm::errors::CommitError),
+    #[fail(display = "Require {:?}", _0)]
+    Require(sputnikvm::errors::RequireError),
     #[fail(display = "Machine has not been initialized")]
     MachineNotInitialized
 }
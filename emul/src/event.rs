@@ -0,0 +1,17 @@
+//! Event logs emitted by `LOG` instructions
+use std::rc::Rc;
+
+/// An event log, and the instruction that emitted it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventLog {
+    /// account the log is emitted from
+    pub address: bigint::H160,
+    pub topics: Vec<bigint::H256>,
+    pub data: Vec<u8>,
+    /// call depth of the frame that emitted the log
+    pub depth: usize,
+    /// code that emitted the log
+    pub code: Rc<Vec<u8>>,
+    /// byte offset of the `LOG` instruction in `code`
+    pub pc: usize,
+}
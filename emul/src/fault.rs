@@ -0,0 +1,62 @@
+//! Frames of the transaction that failed, and why
+use sputnikvm::errors::OnChainError;
+use std::{fmt, rc::Rc};
+
+/// Why a frame failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FailureKind {
+    /// `REVERT`, with the data it returned
+    Revert(Vec<u8>),
+    OutOfGas,
+    InvalidOpcode,
+    StackUnderflow,
+    StackOverflow,
+    /// a jump to a position that is not a `JUMPDEST`
+    InvalidJump,
+    /// any other error of the VM
+    Other(String),
+}
+
+impl From<OnChainError> for FailureKind {
+    fn from(err: OnChainError) -> FailureKind {
+        match err {
+            OnChainError::EmptyGas => FailureKind::OutOfGas,
+            OnChainError::InvalidOpcode => FailureKind::InvalidOpcode,
+            OnChainError::StackUnderflow => FailureKind::StackUnderflow,
+            OnChainError::StackOverflow => FailureKind::StackOverflow,
+            OnChainError::BadJumpDest => FailureKind::InvalidJump,
+            other => FailureKind::Other(format!("{:?}", other)),
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FailureKind::Revert(data) if data.is_empty() => write!(f, "reverted"),
+            FailureKind::Revert(data) => {
+                write!(f, "reverted with 0x")?;
+                data.iter().try_for_each(|b| write!(f, "{:02x}", b))
+            },
+            FailureKind::OutOfGas => write!(f, "out of gas"),
+            FailureKind::InvalidOpcode => write!(f, "invalid opcode"),
+            FailureKind::StackUnderflow => write!(f, "stack underflow"),
+            FailureKind::StackOverflow => write!(f, "stack overflow"),
+            FailureKind::InvalidJump => write!(f, "invalid jump destination"),
+            FailureKind::Other(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// A frame that failed, and the instruction it failed at
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub kind: FailureKind,
+    /// call depth of the frame
+    pub depth: usize,
+    /// address the failing code was loaded from
+    pub code_address: bigint::H160,
+    pub code: Rc<Vec<u8>>,
+    /// byte offset of the failing instruction in `code`
+    pub pc: usize,
+}
@@ -0,0 +1,52 @@
+//! Call frames of the transaction being emulated
+use sputnikvm::Opcode;
+use std::rc::Rc;
+
+/// How a call frame was entered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+    Create,
+}
+
+impl FrameKind {
+    /// The kind of frame an opcode enters, if it enters one
+    crate fn from_opcode(opcode: Opcode) -> Option<FrameKind> {
+        match opcode {
+            Opcode::CALL => Some(FrameKind::Call),
+            Opcode::CALLCODE => Some(FrameKind::CallCode),
+            Opcode::DELEGATECALL => Some(FrameKind::DelegateCall),
+            Opcode::STATICCALL => Some(FrameKind::StaticCall),
+            Opcode::CREATE => Some(FrameKind::Create),
+            _ => None
+        }
+    }
+}
+
+/// One machine on the call stack of the VM
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub kind: FrameKind,
+    /// address of the account whose storage and balance the frame executes against
+    pub address: bigint::H160,
+    /// address the executing code was loaded from.
+    /// Differs from `address` for `DELEGATECALL` and `CALLCODE`
+    pub code_address: bigint::H160,
+    /// code executing in this frame
+    pub code: Rc<Vec<u8>>,
+    /// bytecode position of the instruction in the parent frame that entered this frame.
+    /// `None` for the frame of the transaction itself
+    pub call_site: Option<usize>,
+    /// number of instructions that had been stepped when the frame was entered
+    pub entered: usize,
+}
+
+impl Frame {
+    /// Whether this frame runs initialization code
+    pub fn is_creation(&self) -> bool {
+        self.kind == FrameKind::Create
+    }
+}
@@ -1,9 +1,30 @@
 #![feature(crate_visibility_modifier)]
 pub mod emulator;
+pub mod disasm;
+mod cache;
 mod err;
+mod event;
+mod fault;
+mod frame;
+mod node;
+mod overrides;
 mod scary;
+mod session;
+mod simpledb;
+mod trace;
+mod write;
 
 pub use self::emulator::Action;
+pub use self::frame::{Frame, FrameKind};
+pub use self::write::Write;
+pub use self::fault::{Failure, FailureKind};
+pub use self::event::EventLog;
+pub use self::trace::{StructLog, Trace};
+pub use self::session::{Session, Recorder, Replay};
+pub use self::cache::StateCache;
+pub use self::overrides::StateOverride;
 pub use sputnikvm::ValidTransaction;
 pub use sputnikvm::HeaderParams;
+pub use sputnikvm::Patch;
+pub use sputnikvm_network_foundation::{FrontierPatch, HomesteadPatch, SpuriousDragonPatch, ByzantiumPatch};
 pub use web3::{Web3, Transport};
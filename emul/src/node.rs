@@ -0,0 +1,64 @@
+//! Fetches blocks and transactions from an Ethereum node and converts them into the types the VM expects
+use std::rc::Rc;
+use sputnikvm::{HeaderParams, ValidTransaction, TransactionAction};
+use web3::{api::Web3, Transport, types::{Block, BlockId, BlockNumber, H256, Transaction, TransactionId}};
+use futures::future::Future;
+use super::err::EmulError;
+
+/// Get a block from the node, failing if the node does not know about it
+crate fn block<T>(client: &Web3<T>, id: BlockId) -> Result<Block<H256>, EmulError> where T: Transport {
+    client.eth()
+        .block(id.clone())
+        .wait()?
+        .ok_or_else(|| EmulError::BlockNotFound(format!("{:?}", id)))
+}
+
+/// Get a block from the node, including the full transactions it contains
+crate fn block_with_txs<T>(client: &Web3<T>, id: BlockId) -> Result<Block<Transaction>, EmulError> where T: Transport {
+    client.eth()
+        .block_with_txs(id.clone())
+        .wait()?
+        .ok_or_else(|| EmulError::BlockNotFound(format!("{:?}", id)))
+}
+
+/// Get a transaction from the node, failing if the node does not know about it
+crate fn transaction<T>(client: &Web3<T>, hash: H256) -> Result<Transaction, EmulError> where T: Transport {
+    client.eth()
+        .transaction(TransactionId::Hash(hash))
+        .wait()?
+        .ok_or(EmulError::TransactionNotFound(hash))
+}
+
+/// The number of a mined block. Pending blocks do not have a number
+crate fn number<TX>(block: &Block<TX>) -> Result<BlockNumber, EmulError> {
+    let number = block.number.ok_or_else(|| EmulError::BlockNotFound("Pending".to_string()))?;
+    Ok(BlockNumber::Number(number.as_u64()))
+}
+
+/// Header parameters for the VM, derived from a block
+crate fn header_params<TX>(block: &Block<TX>) -> Result<HeaderParams, EmulError> {
+    let number = block.number.ok_or_else(|| EmulError::BlockNotFound("Pending".to_string()))?;
+    Ok(HeaderParams {
+        beneficiary: bigint::H160(block.author.0),
+        timestamp: block.timestamp.as_u64(),
+        number: bigint::U256::from(number.as_u64()),
+        difficulty: bigint::U256(block.difficulty.0),
+        gas_limit: bigint::Gas::from(block.gas_limit.as_u64()),
+    })
+}
+
+/// A transaction the VM can execute, built from a transaction that was sent to the node
+crate fn valid_transaction(tx: &Transaction) -> ValidTransaction {
+    ValidTransaction {
+        caller: Some(bigint::H160(tx.from.0)),
+        gas_price: bigint::Gas::from(bigint::U256(tx.gas_price.0)),
+        gas_limit: bigint::Gas::from(bigint::U256(tx.gas.0)),
+        action: match tx.to {
+            Some(addr) => TransactionAction::Call(bigint::H160(addr.0)),
+            None => TransactionAction::Create
+        },
+        value: bigint::U256(tx.value.0),
+        input: Rc::new(tx.input.0.clone()),
+        nonce: bigint::U256(tx.nonce.0),
+    }
+}
@@ -0,0 +1,51 @@
+//! Running a transaction against state other than what the node holds
+use std::collections::HashMap;
+
+/// State of an account to execute against instead of what the node holds, like the state
+/// overrides of `eth_call`. Fields left `None`, and slots of storage not given, are read from the
+/// node as usual
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateOverride {
+    pub balance: Option<bigint::U256>,
+    pub nonce: Option<bigint::U256>,
+    pub code: Option<Vec<u8>>,
+    pub storage: HashMap<bigint::U256, bigint::M256>,
+}
+
+impl StateOverride {
+    /// Override storage slot `index` with `value`
+    pub fn with_storage(mut self, index: bigint::U256, value: bigint::M256) -> Self {
+        self.storage.insert(index, value);
+        self
+    }
+
+    /// Add the overrides of `other`, which take precedence over these
+    pub fn merge(&mut self, other: StateOverride) {
+        self.balance = other.balance.or(self.balance);
+        self.nonce = other.nonce.or(self.nonce);
+        if other.code.is_some() {
+            self.code = other.code;
+        }
+        self.storage.extend(other.storage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculate::speculate;
+
+    speculate! {
+        it "merges overrides, keeping the latest" {
+            let slot = bigint::U256::from(0u64);
+            let mut state = StateOverride { balance: Some(bigint::U256::from(1u64)), ..StateOverride::default() }
+                .with_storage(slot, bigint::M256::from(5u64));
+            let later = StateOverride { nonce: Some(bigint::U256::from(2u64)), ..StateOverride::default() }
+                .with_storage(slot, bigint::M256::from(7u64));
+            state.merge(later);
+            assert_eq!(state.balance, Some(bigint::U256::from(1u64)));
+            assert_eq!(state.nonce, Some(bigint::U256::from(2u64)));
+            assert_eq!(state.storage[&slot], bigint::M256::from(7u64));
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Recording what a node tells a debugging session, and answering the session again without the node.
+//! Every account, slot of storage, piece of code, block and transaction the VM and debugger asked
+//! for is recorded, so a session can be debugged offline
+use std::{rc::Rc, path::PathBuf, collections::BTreeMap};
+use futures::future::{self, Future};
+use jsonrpc_core::{Call, Value};
+use serde_derive::{Serialize, Deserialize};
+use web3::{Transport, RequestId};
+use super::err::EmulError;
+use super::simpledb::{SimpleDB, Batched};
+
+/// Responses of a node to the requests of a session, by method and parameters
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    responses: BTreeMap<String, Value>,
+}
+
+impl Session {
+    /// Read a session from a file written by a `Recorder`
+    pub fn open(path: PathBuf) -> Result<Self, EmulError> {
+        SimpleDB::<Session>::new(path)?.get()
+    }
+
+    /// Number of responses recorded
+    pub fn len(&self) -> usize {
+        self.responses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.responses.is_empty()
+    }
+}
+
+// requests are recorded by their method and parameters, leaving out the id
+fn key(call: &Call) -> Option<String> {
+    match call {
+        Call::MethodCall(method) => Some(format!("{} {}", method.method, serde_json::to_string(&method.params).ok()?)),
+        _ => None
+    }
+}
+
+/// A transport that forwards requests to a node, and records the responses to a file.
+/// Responses are written in batches, and the rest once the recorder and all its clones are dropped
+#[derive(Debug, Clone)]
+pub struct Recorder<T: Transport> {
+    inner: T,
+    session: Rc<Batched<Session>>,
+}
+
+impl<T: Transport> Recorder<T> {
+    /// Record the responses of `inner` to `path`
+    pub fn new(inner: T, path: PathBuf) -> Result<Self, EmulError> {
+        Ok(Recorder { inner, session: Rc::new(Batched::new(SimpleDB::new(path)?, Session::default())) })
+    }
+
+    /// The responses recorded so far
+    pub fn session(&self) -> Session {
+        self.session.data().clone()
+    }
+}
+
+impl<T> Transport for Recorder<T> where T: Transport, T::Out: 'static {
+    type Out = Box<dyn Future<Item = Value, Error = web3::Error>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        self.inner.prepare(method, params)
+    }
+
+    fn send(&self, id: RequestId, request: Call) -> Self::Out {
+        let key = key(&request);
+        let session = self.session.clone();
+        Box::new(self.inner.send(id, request).map(move |value| {
+            if let Some(key) = key {
+                session.change(|session| session.responses.insert(key, value.clone()) != Some(value.clone()));
+            }
+            value
+        }))
+    }
+}
+
+/// A transport that answers requests with the responses of a recorded session, without a node.
+/// Requests the session did not make fail
+#[derive(Debug, Clone)]
+pub struct Replay {
+    session: Rc<Session>,
+}
+
+impl Replay {
+    pub fn new(session: Session) -> Self {
+        Replay { session: Rc::new(session) }
+    }
+
+    /// Answer with the session recorded to `path`
+    pub fn open(path: PathBuf) -> Result<Self, EmulError> {
+        Ok(Self::new(Session::open(path)?))
+    }
+}
+
+impl Transport for Replay {
+    type Out = Box<dyn Future<Item = Value, Error = web3::Error>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        (0, web3::helpers::build_request(0, method, params))
+    }
+
+    fn send(&self, _id: RequestId, request: Call) -> Self::Out {
+        let key = key(&request).unwrap_or_else(|| format!("{:?}", request));
+        match self.session.responses.get(&key) {
+            Some(value) => Box::new(future::ok(value.clone())),
+            None => Box::new(future::err(web3::error::ErrorKind::Transport(format!("`{}` was not recorded", key)).into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculate::speculate;
+    use edb_test_helpers as edbtest;
+
+    speculate! {
+        it "replays what it recorded" {
+            let path = std::env::temp_dir().join("edb-session-test");
+            let _ = std::fs::remove_file(&path);
+            let recorder = Recorder::new(edbtest::MockWeb3Transport::default(), path.clone()).unwrap();
+            let accounts = web3::Web3::new(recorder).eth().accounts().wait().unwrap();
+            let replay = Replay::open(path).unwrap();
+            assert_eq!(web3::Web3::new(replay).eth().accounts().wait().unwrap(), accounts);
+        }
+    }
+}
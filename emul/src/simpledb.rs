@@ -7,6 +7,7 @@ use std::{
     marker::PhantomData,
     path::PathBuf,
     default::Default,
+    cell::{Cell, Ref, RefCell},
 };
 use flate2::{
     Compression,
@@ -42,6 +43,7 @@ impl<D> SimpleDB<D> where D: DeserializeOwned + Serialize + Default {
     /// Save structure to a file, serializing to JSON and then compressing with DEFLATE
     crate fn save(&self, data: D) -> Result<(), EmulError> {
         self.mutate(|file| {
+            file.set_len(0).map_err(|e| EmulError::State(StateError::Io(e)))?;
             let ser_data = serde_json::ser::to_vec(&data).map_err(|e| EmulError::State(StateError::Decoder(e)))?;
             let mut e = DeflateEncoder::new(file, Compression::default());
             e.write_all(ser_data.as_slice()).map_err(|e| EmulError::State(StateError::Io(e)))?;
@@ -94,13 +96,74 @@ impl<D> SimpleDB<D> where D: DeserializeOwned + Serialize + Default {
     }
 }
 
+/// changes made to a `Batched` before it is saved again
+const SAVE_EVERY: usize = 64;
+
+/// Data kept in memory and saved to a `SimpleDB` in batches, every `SAVE_EVERY` changes and once
+/// more when it is dropped. Saving rewrites the whole file, so saving every change would cost time
+/// quadratic in the number of changes
+#[derive(Debug)]
+crate struct Batched<D: DeserializeOwned + Serialize + Default + Clone> {
+    db: SimpleDB<D>,
+    data: RefCell<D>,
+    unsaved: Cell<usize>,
+}
+
+impl<D> Batched<D> where D: DeserializeOwned + Serialize + Default + Clone {
+    /// Keep `data`, replacing what is saved in `db`
+    crate fn new(db: SimpleDB<D>, data: D) -> Self {
+        Batched { db, data: RefCell::new(data), unsaved: Cell::new(0) }
+    }
+
+    /// Keep what is saved in `db`
+    crate fn open(db: SimpleDB<D>) -> Result<Self, EmulError> {
+        let data = db.get()?;
+        Ok(Self::new(db, data))
+    }
+
+    crate fn data(&self) -> Ref<'_, D> {
+        self.data.borrow()
+    }
+
+    /// Change the data with `fun`, which returns whether it changed anything
+    crate fn change<F>(&self, fun: F) where F: FnOnce(&mut D) -> bool {
+        if !fun(&mut self.data.borrow_mut()) {
+            return;
+        }
+        self.unsaved.set(self.unsaved.get() + 1);
+        if self.unsaved.get() >= SAVE_EVERY {
+            if let Err(e) = self.save() {
+                error!("Could not save {}: {}", self.db.path.display(), e);
+            }
+        }
+    }
+
+    /// Save any changes that have not been saved yet
+    crate fn save(&self) -> Result<(), EmulError> {
+        if self.unsaved.get() == 0 {
+            return Ok(());
+        }
+        self.db.save(self.data.borrow().clone())?;
+        self.unsaved.set(0);
+        Ok(())
+    }
+}
+
+impl<D> Drop for Batched<D> where D: DeserializeOwned + Serialize + Default + Clone {
+    fn drop(&mut self) {
+        if let Err(e) = self.save() {
+            error!("Could not save {}: {}", self.db.path.display(), e);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::collections::HashMap;
     #[test]
     fn save() {
-        pretty_env_logger::try_init();
+        let _ = pretty_env_logger::try_init();
         let db = SimpleDB::<HashMap<String, usize>>::new(PathBuf::from("/tmp/SOME")).unwrap();
         let mut data = HashMap::new();
         data.insert("Hello".to_string(), 45);
@@ -110,7 +173,7 @@ mod tests {
 
     #[test]
     fn get() {
-        pretty_env_logger::try_init();
+        let _ = pretty_env_logger::try_init();
         let db = SimpleDB::<HashMap<String, usize>>::new(PathBuf::from("/tmp/SOME")).unwrap();
         let mut data = HashMap::new();
         data.insert("Hello".to_string(), 45);
@@ -118,4 +181,17 @@ mod tests {
         db.save(data.clone()).unwrap();
         info!("DATA: {:?}", db.get().unwrap());
     }
+
+    #[test]
+    fn save_batched() {
+        let _ = pretty_env_logger::try_init();
+        let path = PathBuf::from("/tmp/SOME_BATCHED");
+        let _ = fs::remove_file(&path);
+        let batched = Batched::new(SimpleDB::<HashMap<String, usize>>::new(path.clone()).unwrap(), HashMap::new());
+        batched.change(|data| data.insert("Hello".to_string(), 45).is_none());
+        assert!(SimpleDB::<HashMap<String, usize>>::new(path.clone()).unwrap().get().unwrap().is_empty());
+        drop(batched);
+        let saved = SimpleDB::<HashMap<String, usize>>::new(path).unwrap().get().unwrap();
+        assert_eq!(saved.get("Hello"), Some(&45));
+    }
 }
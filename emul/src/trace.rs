@@ -0,0 +1,77 @@
+//! Execution traces in the format of the default tracer of geth's `debug_traceTransaction`
+use std::collections::BTreeMap;
+use failure::Error;
+use serde_derive::Serialize;
+use sputnikvm::Opcode;
+use super::{disasm, write::hex};
+
+/// The state before one instruction was executed. Words are written as 64 hex digits, without a
+/// `0x` prefix
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructLog {
+    /// byte offset of the instruction
+    pub pc: usize,
+    pub op: String,
+    /// gas left to the frame before the instruction
+    pub gas: u64,
+    pub gas_cost: u64,
+    /// call depth of the frame, counting the outermost frame as 1
+    pub depth: usize,
+    /// why the instruction failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// the stack, top of the stack last
+    pub stack: Vec<String>,
+    /// the memory, one word at a time
+    pub memory: Vec<String>,
+    /// slots of the storage of the executing account that the transaction has read or written
+    pub storage: BTreeMap<String, String>,
+    /// account the instruction executed against
+    #[serde(skip)]
+    pub address: bigint::H160,
+}
+
+/// The trace of a transaction
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Trace {
+    /// gas used by the transaction
+    pub gas: u64,
+    pub failed: bool,
+    /// hex of the data the transaction returned
+    pub return_value: String,
+    pub struct_logs: Vec<StructLog>,
+}
+
+impl Trace {
+    /// The trace as JSON
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Name of an opcode as geth writes it
+pub fn op_name(opcode: Opcode) -> String {
+    match disasm::name(opcode).as_str() {
+        "SUICIDE" => "SELFDESTRUCT".to_string(),
+        name => name.to_string()
+    }
+}
+
+/// A word written as 64 hex digits
+pub fn word(value: bigint::U256) -> String {
+    hex(&bigint::H256::from(value).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speculate::speculate;
+
+    speculate! {
+        it "writes words in full" {
+            assert_eq!(word(bigint::U256::from(0x80)), format!("{}80", "0".repeat(62)));
+        }
+    }
+}
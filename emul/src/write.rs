@@ -0,0 +1,38 @@
+//! Writes to storage and memory made by executed instructions
+use std::fmt;
+
+/// A write made by one instruction, with the value it replaced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Write {
+    /// `SSTORE` to the storage of `address`
+    Storage {
+        address: bigint::H160,
+        slot: bigint::U256,
+        old: bigint::M256,
+        new: bigint::M256,
+    },
+    /// a write to the memory of the frame at call depth `depth`
+    Memory {
+        depth: usize,
+        offset: usize,
+        old: Vec<u8>,
+        new: Vec<u8>,
+    },
+}
+
+impl fmt::Display for Write {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Write::Storage { address, slot, old, new } => {
+                write!(f, "storage slot {:#x} of {:#x}: {:#x} -> {:#x}", slot, address, old.0, new.0)
+            },
+            Write::Memory { offset, old, new, .. } => {
+                write!(f, "memory {:#x}..{:#x}: 0x{} -> 0x{}", offset, offset + new.len(), hex(old), hex(new))
+            }
+        }
+    }
+}
+
+crate fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
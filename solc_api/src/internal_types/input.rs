@@ -15,6 +15,7 @@ pub enum FoundationVersion {
    Byzantium,
    Frontier,
    Homestead,
+   #[serde(rename = "spuriousDragon")]
    SpuriousDragon,
 }
 
@@ -116,6 +117,8 @@ impl Default for Settings {
     fn default() -> Settings {
         let mut item = HashMap::new();
         item.insert("*".to_string(), vec![SolcItem::Abi, SolcItem::Ast,
+                              SolcItem::Bytecode(EvmOpt::BytecodeObject),
+                              SolcItem::Bytecode(EvmOpt::SourceMap),
                               SolcItem::DeployedBytecode(EvmOpt::BytecodeObject),
                               SolcItem::DeployedBytecode(EvmOpt::SourceMap)]);
         let mut output = HashMap::new();
@@ -8,6 +8,7 @@ use std::{
 use serde_derive::*;
 use serde::de::{self, Deserialize, Deserializer, Visitor, MapAccess};
 use { ethabi, hex };
+use serde_json::Value;
 
 use err::SolcApiError;
 
@@ -88,7 +89,7 @@ pub(crate) struct RawCompiledSource {
 #[derive(Debug, Clone, Deserialize)]
 pub struct CompiledSourceFile {
     pub id: usize,
-    #[serde(skip_deserializing)]
+    #[serde(default)]
     pub ast: Ast,
     #[serde(skip_deserializing)]
     pub legacy_ast: LegacyAst // Not Implemented
@@ -113,9 +114,16 @@ pub struct LegacyAssembly;
 #[derive(Debug, Clone, Default, Deserialize)]
 /// Unimplemented!
 pub struct MethodIdentifiers;
+/// The JSON AST of a source file
 #[derive(Debug, Clone, Default, Deserialize)]
-/// Unimplemented!
-pub struct Ast;
+pub struct Ast(Value);
+
+impl Ast {
+    /// The root node of the AST
+    pub fn json(&self) -> &Value {
+        &self.0
+    }
+}
 
 #[derive(Debug, Clone, Deserialize)]
 struct RawContract {
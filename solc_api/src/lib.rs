@@ -34,7 +34,7 @@ pub struct SolcApiBuilder {
     /// specify the path of the source code
     source: PathBuf,
     /// EvmVersion to use
-    version: Option<String>,
+    version: Option<FoundationVersion>,
     /// [FLAG] whether to optimize output
     optimize: Option<bool>,
 }
@@ -49,7 +49,7 @@ impl SolcApiBuilder {
     /// Version
     pub fn evm_version(&mut self, ver: FoundationVersion) -> &mut Self {
         let new = self;
-        new.version = Some(ver.into());
+        new.version = Some(ver);
         new
     }
     /// Whether to optimize output
@@ -64,6 +64,7 @@ impl SolcApiBuilder {
     // TODO: Make work for multiple input files
     pub fn build(&self) -> String {
         let mut default = StandardJson::default();
+        default.settings.evm_version = self.version.clone();
         let source_path = self.source
             .clone()
             .canonicalize()
@@ -114,7 +115,7 @@ mod tests {
     fn ser_compilation_object() {
         let obj = StandardJson::default();
         let ser = serde_json::to_string(&obj).unwrap();
-        assert_eq!(ser, r#"{"language":"Solidity","sources":{},"settings":{"outputSelection":{"*":{"*":["abi","ast","evm.deployedBytecode.object","evm.deployedBytecode.sourceMap"]}}}}"#);
+        assert_eq!(ser, r#"{"language":"Solidity","sources":{},"settings":{"outputSelection":{"*":{"*":["abi","ast","evm.bytecode.object","evm.bytecode.sourceMap","evm.deployedBytecode.object","evm.deployedBytecode.sourceMap"]}}}}"#);
     }
 
     #[test]
@@ -6,6 +6,7 @@
 //! the solidity/simple.bin/SimpleStorage.bin is returned.
 //! all other values are 0/uninitialized values
 //! a balance of 150,000,000 wei is used for getBalance
+//! one block is mined, `MINED_BLOCK_HASH`, holding the transactions `MINED_SET_TX` and `MINED_GET_TX`
 
 use web3::{Transport, RequestId};
 use futures::future::Future;
@@ -43,6 +44,8 @@ impl MockWeb3Transport {
             _ => panic!("Only method calls supported for mockweb3")
         };
         let mut addr: Option<String> = None;
+        // whether blocks are asked for with their transactions in full
+        let mut full = false;
         if let Some(x) = method.params.clone() {
             info!("method.params: {:?}", method.params);
             let params: Value = x.parse().expect("Mock parse failure");
@@ -51,6 +54,7 @@ impl MockWeb3Transport {
             } else {
                 addr = Some(serde_json::from_value(params.get(0).expect("conditional scope").clone()).expect("Mock failure; deserialize"));
             }
+            full = params.get(1).and_then(Value::as_bool).unwrap_or(false);
         }
 
         // let val: Value = serde_json::from_str()
@@ -84,6 +88,26 @@ impl MockWeb3Transport {
                 serde_json::from_str(r#""0xffffff0000000000000000000000000000000000000000000000000000000000""#)
                     .expect("Could not decode arbitrary storage json")
             },
+            "eth_chainId" => {
+                info!("Returning chain id 1");
+                json!("0x1")
+            },
+            "eth_getTransactionByHash" => {
+                let hash = addr.expect("No hash supplied in parameters to mock RPC!");
+                mined_transactions().into_iter().find(|tx| tx["hash"] == json!(hash)).unwrap_or(Value::Null)
+            },
+            "eth_getBlockByHash" => {
+                match addr {
+                    Some(ref hash) if hash == MINED_BLOCK_HASH => mined_block(full),
+                    _ => Value::Null
+                }
+            },
+            "eth_getBlockByNumber" => {
+                match addr {
+                    Some(ref number) if *number == format!("{:#x}", MINED_BLOCK_NUMBER) => mined_block(full),
+                    _ => Value::Null
+                }
+            },
             "eth_accounts" => {
                 let json = json!([
                     SIMPLE_STORAGE_ADDR,
@@ -98,6 +122,57 @@ impl MockWeb3Transport {
     }
 }
 
+// the transactions of the mined block
+fn mined_transactions() -> Vec<Value> {
+    let word = |n: u64| format!("{:064x}", n);
+    let transaction = |hash: &str, index: u64, input: String| json!({
+        "hash": hash,
+        "nonce": format!("{:#x}", index),
+        "blockHash": MINED_BLOCK_HASH,
+        "blockNumber": format!("{:#x}", MINED_BLOCK_NUMBER),
+        "transactionIndex": format!("{:#x}", index),
+        "from": format!("0x{}", ADDR_CALLER),
+        "to": SIMPLE_STORAGE_ADDR,
+        "value": "0x0",
+        "gasPrice": "0x1",
+        "gas": "0x186a0",
+        "input": input,
+    });
+    vec![
+        // `set(1337)`
+        transaction(MINED_SET_TX, 0, format!("0x60fe47b1{}", word(1337))),
+        // `get()`
+        transaction(MINED_GET_TX, 1, "0x6d4ce63c".to_string()),
+    ]
+}
+
+// the mined block, with its transactions in full or only their hashes
+fn mined_block(full: bool) -> Value {
+    let zero = format!("0x{}", "0".repeat(64));
+    let transactions = mined_transactions().into_iter().map(|tx| if full { tx } else { tx["hash"].clone() }).collect::<Vec<_>>();
+    json!({
+        "hash": MINED_BLOCK_HASH,
+        "parentHash": zero,
+        "sha3Uncles": zero,
+        "miner": format!("0x{}", MINER),
+        "stateRoot": zero,
+        "transactionsRoot": zero,
+        "receiptsRoot": zero,
+        "number": format!("{:#x}", MINED_BLOCK_NUMBER),
+        "gasUsed": "0x0",
+        "gasLimit": "0x4c4b400",
+        "extraData": "0x",
+        "logsBloom": format!("0x{}", "0".repeat(512)),
+        "timestamp": "0x5b91e14d",
+        "difficulty": "0xbd6180d5d1ec8",
+        "totalDifficulty": "0x0",
+        "sealFields": [],
+        "uncles": [],
+        "transactions": transactions,
+        "size": "0x0",
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
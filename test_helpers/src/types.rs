@@ -30,6 +30,16 @@ pub const ADDR_CALLER: &'static str = "94143ba98cdd5a0f3a80a6514b74c25b5bdb9b59"
 /// miner/beneficiary
 pub const MINER: &'static str = "11f275d2ad4390c41b150fa0efb5fb966dbc714d";
 
+// A mined block, holding a transaction that sets SimpleStorage to 1337 and one that gets it after
+/// Hash of the mined block
+pub const MINED_BLOCK_HASH: &'static str = "0x5c1f3b0e4a4d4e2d2ac6b1f0f7d0e2a4c1b3e8f1d2c3b4a5968778695a4b3c2d";
+/// Number of the mined block
+pub const MINED_BLOCK_NUMBER: u64 = 6285997;
+/// Hash of the transaction calling `set(1337)` on SimpleStorage, first in the mined block
+pub const MINED_SET_TX: &'static str = "0x1e0f7c7a8e1d6f0a4b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f607182";
+/// Hash of the transaction calling `get()` on SimpleStorage, second in the mined block
+pub const MINED_GET_TX: &'static str = "0x2f1e8d8b9f2e7a1b5c3d4e5f6071829304b5c6d7e8f90a1b2c3d4e5f60718293";
+
 
 // TODO: include import contracts
 /// Simple storage bytecode